@@ -1,18 +1,67 @@
-use std::{fs::File, io::BufReader, num::NonZeroU32};
-
-use image::{ImageError, codecs::png::PngDecoder, ImageDecoder};
+use std::{fs::File, io::{BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom}, num::NonZeroU32};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::{Deref, DerefMut};
+
+use image::{ImageError, codecs::png::{PngDecoder, PngEncoder}, ImageDecoder, ImageEncoder, ExtendedColorType, ImageFormat};
+use image::codecs::jpeg::JpegDecoder;
+use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
 //use image::{ImageError, codecs::png::PngDecoder, ImageDecoder};
-use softbuffer::Buffer;
+use softbuffer::{Buffer, SoftBufferError, Surface};
+use winit::dpi::PhysicalSize;
 use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 
 use zerocopy::AsBytes;
 
-use crate::rgba::Rgba;
+use crate::font::{BitmapFont, TextAlign};
+use crate::rgba::{Color, Rgba};
 
 pub struct Canvas<'a, D, W> {
-    buffer: Buffer<'a, D, W>,
+    buffer: Pixels<'a, D, W>,
     width: NonZeroU32,
-    height: NonZeroU32
+    height: NonZeroU32,
+    clip: Option<(i64, i64, i64, i64)>,
+    origin: (i64, i64),
+    supersample: Option<(Vec<u32>, u32)>
+}
+
+// the pixels behind a canvas, tests own theirs since softbuffer only hands out buffers for a real window
+enum Pixels<'a, D, W> {
+    Buffer(Buffer<'a, D, W>),
+    #[cfg(test)]
+    Owned(Vec<u32>)
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> Deref for Pixels<'_, D, W> {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        match self {
+            Pixels::Buffer(buffer) => buffer,
+            #[cfg(test)]
+            Pixels::Owned(pixels) => pixels
+        }
+    }
+}
+
+impl<D: HasDisplayHandle, W: HasWindowHandle> DerefMut for Pixels<'_, D, W> {
+    fn deref_mut(&mut self) -> &mut [u32] {
+        match self {
+            Pixels::Buffer(buffer) => buffer,
+            #[cfg(test)]
+            Pixels::Owned(pixels) => pixels
+        }
+    }
+}
+
+impl<'a, D, W> Pixels<'a, D, W> {
+    fn into_buffer(self) -> Buffer<'a, D, W> {
+        match self {
+            Pixels::Buffer(buffer) => buffer,
+            #[cfg(test)]
+            Pixels::Owned(_) => unreachable!("owned test pixels have no softbuffer buffer")
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -22,18 +71,186 @@ pub enum ImageCompletion {
     Complete
 }
 
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum BlendMode {
+    Replace,
+    Over,
+    Add,
+    Multiply,
+    Screen
+}
+
+impl BlendMode {
+
+    pub fn apply(self, source: Rgba, destination: Rgba) -> Rgba {
+        use BlendMode::*;
+
+        let channel: fn(u8, u8) -> u8 = match self {
+            Replace => return source,
+            Over => return source.over(destination),
+            Add => |s, d| s.saturating_add(d),
+            Multiply => |s, d| ((s as u16 * d as u16 + 127) / 255) as u8,
+            Screen => |s, d| 255 - (((255 - s) as u16 * (255 - d) as u16 + 127) / 255) as u8
+        };
+
+        let mut out = destination;
+        for c in Color::rgb() {
+            out[c] = channel(source[c], destination[c]);
+        }
+        out
+    }
+}
+
 impl<'a, D: HasDisplayHandle, W: HasWindowHandle> Canvas<'a, D, W> {
 
     pub fn new(buffer: Buffer<'a, D, W>, width: NonZeroU32, height: NonZeroU32) -> Self {
+        Self::from_pixels(Pixels::Buffer(buffer), width, height)
+    }
+
+    fn from_pixels(buffer: Pixels<'a, D, W>, width: NonZeroU32, height: NonZeroU32) -> Self {
         Self {
             buffer,
             width,
-            height
+            height,
+            clip: None,
+            origin: (0, 0),
+            supersample: None
         }
     }
 
+    // draws into a buffer `factor` times larger on each axis, and present_downsampled averages it
+    // back down into the real buffer. width, height, to_image, rows_mut and pixels_mut all work in
+    // the larger space, only present and destroy see the real size
+    pub fn with_supersampling(buffer: Buffer<'a, D, W>, width: NonZeroU32, height: NonZeroU32, factor: u32) -> Self {
+        let mut canvas = Self::new(buffer, width, height);
+        canvas.set_supersampling(width, height, factor);
+        canvas
+    }
+
+    /// Resizes the surface and wraps its next buffer, `None` for a zero sized window or a failed resize.
+    ///
+    /// ```no_run
+    /// use std::rc::Rc;
+    /// use display_lib::{Canvas, Rgba};
+    /// use winit::event::{Event, WindowEvent};
+    /// use winit::event_loop::EventLoop;
+    /// use winit::window::WindowBuilder;
+    ///
+    /// let event_loop = EventLoop::new().unwrap();
+    /// let window = Rc::new(WindowBuilder::new().build(&event_loop).unwrap());
+    /// let context = softbuffer::Context::new(window.clone()).unwrap();
+    /// let mut surface = softbuffer::Surface::new(&context, window.clone()).unwrap();
+    ///
+    /// event_loop.run(move |event, _| {
+    ///     if let Event::WindowEvent { event: WindowEvent::Resized(size), .. } = event {
+    ///         // a minimised window reports a zero size, there is nothing to draw then
+    ///         if let Some(mut canvas) = Canvas::resize_from_surface(&mut surface, size) {
+    ///             canvas.fill(Rgba::BLACK);
+    ///             canvas.present().unwrap();
+    ///         }
+    ///     }
+    /// }).unwrap();
+    /// ```
+    pub fn resize_from_surface(surface: &'a mut Surface<D, W>, size: PhysicalSize<u32>) -> Option<Self> {
+        let width = NonZeroU32::new(size.width)?;
+        let height = NonZeroU32::new(size.height)?;
+
+        surface.resize(width, height).ok()?;
+        let buffer = surface.buffer_mut().ok()?;
+
+        Some(Self::new(buffer, width, height))
+    }
+
+    pub fn set_clip(&mut self, x: i64, y: i64, w: i64, h: i64) {
+        self.clip = Some((x, y, w.max(0), h.max(0)));
+    }
+
+    pub fn clear_clip(&mut self) {
+        self.clip = None;
+    }
+
+    // added to the coordinates of every later draw call, the clip rect stays in screen space
+    pub fn set_origin(&mut self, ox: isize, oy: isize) {
+        self.origin = (ox as i64, oy as i64);
+    }
+
+    // downsamples first like present, so a supersampled frame is not handed back stale
     pub fn destroy(self) -> Buffer<'a, D, W> {
-        self.buffer
+        self.into_pixels().into_buffer()
+    }
+
+    /// Downsamples if supersampling, then presents the buffer.
+    ///
+    /// Softbuffer's present consumes the buffer, so the canvas is consumed too and nothing is handed back.
+    /// The next frame wraps a fresh buffer from the surface, through `surface.buffer_mut()` or
+    /// [`Canvas::resize_from_surface`].
+    ///
+    /// ```no_run
+    /// use std::rc::Rc;
+    /// use display_lib::{Canvas, Rgba};
+    /// use winit::event_loop::EventLoop;
+    /// use winit::window::WindowBuilder;
+    ///
+    /// let event_loop = EventLoop::new().unwrap();
+    /// let window = Rc::new(WindowBuilder::new().build(&event_loop).unwrap());
+    /// let context = softbuffer::Context::new(window.clone()).unwrap();
+    /// let mut surface = softbuffer::Surface::new(&context, window.clone()).unwrap();
+    ///
+    /// for frame in 0..2u8 {
+    ///     let mut canvas = Canvas::resize_from_surface(&mut surface, window.inner_size()).unwrap();
+    ///     canvas.fill(Rgba::new(frame * 100, 0, 0, 255));
+    ///     canvas.present().unwrap();
+    /// }
+    /// ```
+    pub fn present(self) -> Result<(), SoftBufferError> {
+        self.into_pixels().into_buffer().present()
+    }
+
+    // box-averages each factor x factor block into the real buffer, present calls this itself
+    pub fn present_downsampled(&mut self) {
+        let Some((samples, factor)) = &self.supersample else {
+            return;
+        };
+
+        let factor = *factor as usize;
+        let sample_width = self.width.get() as usize;
+        let width = sample_width / factor;
+        let area = (factor * factor) as u32;
+
+        for (block_y, rows) in samples.chunks_exact(sample_width * factor).enumerate() {
+            for block_x in 0..width {
+                let mut sum = [0u32; 4];
+                for row in rows.chunks_exact(sample_width) {
+                    for &pixel in &row[block_x * factor..(block_x + 1) * factor] {
+                        let pixel = Rgba::from(pixel);
+                        for (total, c) in sum.iter_mut().zip(Color::ALL) {
+                            *total += pixel[c] as u32;
+                        }
+                    }
+                }
+
+                let mut average = Rgba::default();
+                for (total, c) in sum.into_iter().zip(Color::ALL) {
+                    average[c] = ((total + area / 2) / area) as u8;
+                }
+                self.buffer[block_y * width + block_x] = average.into();
+            }
+        }
+    }
+
+    // clip and origin are kept, the old buffer is dropped without being presented
+    pub fn resize(&mut self, buffer: Buffer<'a, D, W>, width: NonZeroU32, height: NonZeroU32) {
+        self.replace_pixels(Pixels::Buffer(buffer), width, height);
+    }
+
+    fn replace_pixels(&mut self, buffer: Pixels<'a, D, W>, width: NonZeroU32, height: NonZeroU32) {
+        self.buffer = buffer;
+        self.width = width;
+        self.height = height;
+
+        if let Some((_, factor)) = self.supersample {
+            self.set_supersampling(width, height, factor);
+        }
     }
 
     pub fn width(&self) -> NonZeroU32 {
@@ -45,250 +262,3417 @@ impl<'a, D: HasDisplayHandle, W: HasWindowHandle> Canvas<'a, D, W> {
     }
 
     pub fn fill(&mut self, color: Rgba) {
-        self.buffer.fill(color.into());
+        if self.clip.is_none() {
+            return self.data_mut().fill(color.into());
+        }
+
+        let (left, top, right, bottom) = self.bounds();
+        for gy in top..bottom {
+            self.fill_span(left, right - 1, gy, color);
+        }
     }
 
-    pub fn draw_image<R: ColorRect<Rgba>>(&mut self, x: isize, y: isize, image: &R) {
-        let bytes = image.get_bytes();
+    #[cfg(feature = "rayon")]
+    pub fn fill_parallel(&mut self, color: Rgba) {
+        use rayon::prelude::*;
 
-        let mut gx = x;
-        let mut gy = y;
+        // below this many pixels spawning the work costs more than the fill
+        const PARALLEL_THRESHOLD: usize = 1 << 16;
+        const CHUNK: usize = 1 << 14;
 
-    let wx = self.width.get() as isize;
-    let wy = self.height.get() as isize;
+        if self.clip.is_some() || self.data().len() < PARALLEL_THRESHOLD {
+            return self.fill(color);
+        }
 
-    for counter in 0..image.get_bytes().len() {
+        let color: u32 = color.into();
+        self.data_mut().par_chunks_mut(CHUNK).for_each(|chunk| chunk.fill(color));
+    }
 
-        if gx < wx && gy < wy && gx >= 0 && gy >= 0 {
-            self.buffer[(gy * wx + gx) as usize] = bytes[counter].into();
-        }
+    pub fn set_pixel(&mut self, x: isize, y: isize, color: Rgba) {
+        self.plot(x as i64, y as i64, color);
+    }
+
+    pub fn get_pixel(&self, x: isize, y: isize) -> Option<Rgba> {
+        let (wx, wy) = (self.width.get() as i64, self.height.get() as i64);
+        let (sx, sy) = (x as i64 + self.origin.0, y as i64 + self.origin.1);
 
-        if gx == image.get_width() as isize + x - 1 {
-            gx = x;
-            gy += 1;
+        if sx >= 0 && sy >= 0 && sx < wx && sy < wy {
+            Some(self.data()[(sy * wx + sx) as usize].into())
         } else {
-            gx += 1;
+            None
         }
     }
-}
 
+    pub fn to_image(&self) -> Image {
+        Image {
+            bytes: self.data().iter().map(|&pixel| Rgba::from(pixel)).collect(),
+            width: self.width.get(),
+            height: self.height.get()
+        }
+    }
 
+    // raw scanlines in screen space, clip and origin don't apply
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u32]> {
+        let width = self.width.get() as usize;
+        self.data_mut().chunks_exact_mut(width)
+    }
 
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = (u32, u32, &mut u32)> {
+        self.rows_mut().zip(0..).flat_map(|(row, y)| {
+            row.iter_mut().zip(0..).map(move |(pixel, x)| (x, y, pixel))
+        })
+    }
 
+    pub fn flood_fill(&mut self, x: isize, y: isize, fill: Rgba) {
+        let target: u32 = match self.get_pixel(x, y) {
+            Some(color) if color != fill => color.into(),
+            _ => return,
+        };
 
+        let (left, top, right, bottom) = self.bounds();
+        let inside = |x: i64, y: i64| x >= left && y >= top && x < right && y < bottom;
 
-pub fn draw_monochrome_image<R: ColorRect<u8, u8>, C: Into<u32>>
+        let (x, y) = (x as i64, y as i64);
+        if !inside(x, y) {
+            return;
+        }
 
-    (
-        &mut self,
-        x: isize, y: isize,
-        image: &R,
-        black: Rgba,
-        white: Rgba
-    ) -> ImageCompletion {
+        let mut stack = vec![(x, y)];
 
-        let (wx, wy) = (self.width.get() as isize, self.height.get() as isize);
+        while let Some((gx, gy)) = stack.pop() {
+            let index = self.index(gx, gy);
 
-        if wx < x || wy < y  {
-            return ImageCompletion::None;
+            if self.data()[index] != target {
+                continue;
+            }
+            self.data_mut()[index] = fill.into();
+
+            for (nx, ny) in [(gx - 1, gy), (gx + 1, gy), (gx, gy - 1), (gx, gy + 1)] {
+                if inside(nx, ny) {
+                    stack.push((nx, ny));
+                }
+            }
         }
+    }
 
-        let mut comp = ImageCompletion::Complete;
+    pub fn copy_region(&mut self, src_x: i64, src_y: i64, w: i64, h: i64, dst_x: i64, dst_y: i64) {
+        let (dx, dy) = (dst_x - src_x, dst_y - src_y);
+        let (left, top, right, bottom) = self.bounds();
+
+        // source columns and rows whose destination also lands in bounds
+        let x0 = src_x.max(left).max(left - dx);
+        let x1 = (src_x + w).min(right).min(right - dx);
+        let y0 = src_y.max(top).max(top - dy);
+        let y1 = (src_y + h).min(bottom).min(bottom - dy);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
 
-        let bytes = image.get_bytes();
+        let len = (x1 - x0) as usize;
+        let mut copy_row = |gy: i64| {
+            let (from, to) = (self.index(x0, gy), self.index(x0 + dx, gy + dy));
+            self.data_mut().copy_within(from..from + len, to);
+        };
 
-        let mut gx = x;
-        let mut gy = y;
+        // moving down copies bottom rows first so the source isn't overwritten before it is read
+        if dy > 0 {
+            (y0..y1).rev().for_each(&mut copy_row);
+        } else {
+            (y0..y1).for_each(&mut copy_row);
+        }
+    }
+
+    pub fn draw_image<R: ColorRect<Rgba>>(&mut self, x: isize, y: isize, image: &R) -> ImageCompletion {
+        self.draw_image_with_mode(x, y, image, BlendMode::Replace)
+    }
 
-        for counter in 0..image.get_bytes().len() {
+    pub fn draw_image_blended<R: ColorRect<Rgba>>(&mut self, x: isize, y: isize, image: &R) -> ImageCompletion {
+        self.draw_image_with_mode(x, y, image, BlendMode::Over)
+    }
 
-            if gx >= 0 && gy >= 0 {
+    pub fn draw_image_with_mode<R: ColorRect<Rgba>>(&mut self, x: isize, y: isize, image: &R, mode: BlendMode) -> ImageCompletion {
+        let bytes = image.get_bytes();
+        let image_width = image.get_width() as isize;
 
-                if gx < wx && gy < wy {
+        if image_width == 0 {
+            return ImageCompletion::Complete;
+        }
 
-                    let color = match bytes[counter] {
-                        0 => { black },
-                        255 => {white},
-                        b => { black.blend(white, b) }
-                    };
+        let wx = self.width.get() as isize;
+        let (bl, bt, br, bb) = self.bounds();
+        let (bl, bt, br, bb) = (bl as isize, bt as isize, br as isize, bb as isize);
 
-                    self.buffer[(gy * wx + gx) as usize] = color.into();
-                } else {
-                    comp = ImageCompletion::Partial;
+        // a full-width replace covers one contiguous block of the buffer
+        if mode == BlendMode::Replace && image_width == wx && bl == x && br == x + wx {
+            let rows = (bytes.len() as isize + wx - 1) / wx;
+            let top = (bt - y).clamp(0, rows);
+            let bottom = (bb - y).clamp(top, rows);
+
+            if top < bottom {
+                let source = &bytes[(top * wx) as usize..((bottom * wx) as usize).min(bytes.len())];
+                let start = self.index(x as i64, (y + top) as i64);
+
+                for (pixel, &color) in self.data_mut()[start..start + source.len()].iter_mut().zip(source) {
+                    *pixel = color.into();
                 }
             }
 
-            if gx == image.get_width() as isize + x - 1 {
-                gx = x;
-                gy += 1;
-            } else {
-                gx += 1;
-            }
+            return completion(top < bottom, top > 0 || bottom < rows);
         }
-        comp
+
+        self.blit_rows(x, y, image_width, bytes.chunks(image_width as usize), mode)
     }
 
-    pub fn draw_rectangle(&mut self, x: i64, y: i64, rect_width: i64, rect_height: i64, color: Rgba) {
-        let mut gx = x;
-        let mut gy = y;
-        let (wx, wy) = (self.width.get() as i64, self.height.get() as i64);
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_image_region<R: ColorRect<Rgba>>(&mut self, dx: isize, dy: isize, image: &R, sx: u32, sy: u32, sw: u32, sh: u32) -> ImageCompletion {
+        let bytes = image.get_bytes();
+        let (width, height) = (image.get_width() as usize, image.get_height() as usize);
 
-        for _ in 0..(rect_width * rect_height) {
+        let (sx, sy) = ((sx as usize).min(width), (sy as usize).min(height));
+        let (sw, sh) = ((sw as usize).min(width - sx), (sh as usize).min(height - sy));
 
+        let rows = (sy..sy + sh).map(|row| {
+            let end = (row * width + sx + sw).min(bytes.len());
+            &bytes[(row * width + sx).min(end)..end]
+        });
 
-            if gx >= 0 && gy >= 0  && gx < wx && gy < wy {
-                self.buffer[(gy * wx + gx) as usize] = color.into();
-            }
+        self.blit_rows(dx, dy, sw as isize, rows, BlendMode::Replace)
+    }
 
-            if gx == rect_width + x - 1 {
-                gx = x;
-                gy += 1;
-            } else {
-                gx += 1;
+    pub fn draw_image_scaled<R: ColorRect<Rgba>>(&mut self, x: isize, y: isize, image: &R, scale_x: f32, scale_y: f32) -> ImageCompletion {
+        if !(scale_x.is_finite() && scale_y.is_finite() && scale_x > 0.0 && scale_y > 0.0) {
+            return ImageCompletion::None;
+        }
+
+        let bytes = image.get_bytes();
+        let source_width = image.get_width() as usize;
+        if source_width == 0 {
+            return ImageCompletion::Complete;
+        }
+        let source_height = (image.get_height() as usize).min(bytes.len() / source_width);
+
+        let width = (source_width as f32 * scale_x).round() as i64;
+        let height = (source_height as f32 * scale_y).round() as i64;
+        let (x, y) = (x as i64, y as i64);
+        // huge scales saturate the extent rather than overflowing
+        let (x_end, y_end) = (x.saturating_add(width), y.saturating_add(height));
+
+        let (left, top, right, bottom) = self.bounds();
+        let (x0, x1) = (x.max(left), x_end.min(right));
+        let (y0, y1) = (y.max(top), y_end.min(bottom));
+
+        // sample each destination pixel at its centre
+        let sample = |d: i64, scale: f32, limit: usize| (((d as f32 + 0.5) / scale) as usize).min(limit - 1);
+
+        for gy in y0..y1 {
+            let row = sample(gy - y, scale_y, source_height) * source_width;
+            for gx in x0..x1 {
+                let index = self.index(gx, gy);
+                self.data_mut()[index] = bytes[row + sample(gx - x, scale_x, source_width)].into();
             }
         }
-    }
-}
 
-pub enum ImageHandle {
-    Handle {
-        path: &'static str
-    },
-    Image {
-        path: &'static str,
-        vector: Vec<Rgba>,
-        width: u32,
-        height: u32
+        completion(x0 < x1 && y0 < y1, x0 > x || y0 > y || x1 < x_end || y1 < y_end)
     }
-}
 
-impl ImageHandle {
+    pub fn draw_image_tiled<R: ColorRect<Rgba>>(&mut self, x: isize, y: isize, w: i64, h: i64, image: &R) {
+        let bytes = image.get_bytes();
+        let source_width = image.get_width() as i64;
+        if source_width == 0 {
+            return;
+        }
+        let source_height = (image.get_height() as i64).min(bytes.len() as i64 / source_width);
+        if source_height == 0 || w <= 0 || h <= 0 {
+            return;
+        }
 
-    pub fn load(&mut self) -> Result<(), ImageError> {
-        use ImageHandle::*;
+        let (x, y) = (x as i64, y as i64);
+        let (left, top, right, bottom) = self.bounds();
 
-        match self {
-            Handle {
-                path
-            } => {
+        for gy in y.max(top)..(y + h).min(bottom) {
+            let row = ((gy - y) % source_height * source_width) as usize;
+            for gx in x.max(left)..(x + w).min(right) {
+                let index = self.index(gx, gy);
+                self.data_mut()[index] = bytes[row + ((gx - x) % source_width) as usize].into();
+            }
+        }
+    }
 
-                let file = File::open(*path)?;
-                let file = BufReader::new(file);
-                let png = PngDecoder::new(file)?;
-                let mut buf: Vec<u8> = vec!(0; (png.total_bytes()) as usize);
+    // the centre of the image lands on (cx, cy), positive angles turn clockwise on screen,
+    // fully transparent source pixels are skipped
+    pub fn draw_image_rotated<R: ColorRect<Rgba>>(&mut self, cx: isize, cy: isize, image: &R, angle_rad: f32) {
+        let bytes = image.get_bytes();
+        let source_width = image.get_width() as i64;
+        if source_width == 0 || !angle_rad.is_finite() {
+            return;
+        }
+        let source_height = (image.get_height() as i64).min(bytes.len() as i64 / source_width);
 
-                let (width, height) = png.dimensions();
-                png.read_image(buf.as_bytes_mut())?;
+        // snap the rounding noise away so quarter turns land exactly on pixel centres
+        let snap = |v: f32| if v.abs() < 1e-6 { 0.0 } else { v };
+        let (sin, cos) = angle_rad.sin_cos();
+        let (sin, cos) = (snap(sin), snap(cos));
 
-                let mut vector: Vec<Rgba> = Vec::new();
-                for pixel in 0..(buf.len() / 4) {
+        let (half_width, half_height) = (source_width as f32 / 2.0, source_height as f32 / 2.0);
+        let reach = half_width.hypot(half_height).ceil() as i64 + 1;
+        let (cx, cy) = (cx as i64, cy as i64);
 
-                    let mut color = Rgba::default();
-                    for index in 0..4 {
-                        color[index] = buf[pixel * 4 + index];
-                    }
-                    vector.push(color);
-                }
+        let (left, top, right, bottom) = self.bounds();
 
-                *self = ImageHandle::Image { path, vector, width, height };
+        for gy in (cy - reach).max(top)..(cy + reach + 1).min(bottom) {
+            for gx in (cx - reach).max(left)..(cx + reach + 1).min(right) {
+                let (dx, dy) = ((gx - cx) as f32, (gy - cy) as f32);
+                let u = (cos * dx + sin * dy + half_width).floor();
+                let v = (cos * dy - sin * dx + half_height).floor();
 
-                Ok(())
-            },
-            _ => {
-                Ok(())
+                if u < 0.0 || v < 0.0 || u >= source_width as f32 || v >= source_height as f32 {
+                    continue;
+                }
+
+                let color = bytes[(v as i64 * source_width + u as i64) as usize];
+                if color.alpha() != 0 {
+                    let index = self.index(gx, gy);
+                    self.data_mut()[index] = color.into();
+                }
             }
         }
     }
 
-    pub fn unload(&mut self) {
-        match self {
-            ImageHandle::Handle { path } | ImageHandle::Image { path, vector: _, width: _, height: _ } => {
-                *self = ImageHandle::Handle { path };
-            }
+    // samples between source pixels in premultiplied space and composites over the canvas,
+    // pixels outside the source count as transparent so the edges fade in
+    pub fn draw_image_subpixel<R: ColorRect<Rgba>>(&mut self, x: f32, y: f32, image: &R) -> ImageCompletion {
+        if !(x.is_finite() && y.is_finite()) {
+            return ImageCompletion::None;
         }
-    }
 
-    pub fn to_image(self) -> Option<Image> {
-        match self {
-            ImageHandle::Image { path: _, vector, width, height } => {
-                Some(Image {
-                    bytes: vector.clone(),
-                    width,
-                    height
-                })
-            },
-            ImageHandle::Handle { path: _ } => None
+        let bytes = image.get_bytes();
+        let source_width = image.get_width() as i64;
+        if source_width == 0 {
+            return ImageCompletion::Complete;
+        }
+        let source_height = (image.get_height() as i64).min(bytes.len() as i64 / source_width);
+
+        let (left, top, right, bottom) = self.bounds();
+        let (x0, x1) = (x.floor() as i64, (x + source_width as f32).ceil() as i64);
+        let (y0, y1) = (y.floor() as i64, (y + source_height as f32).ceil() as i64);
+        let (cx0, cx1, cy0, cy1) = (x0.max(left), x1.min(right), y0.max(top), y1.min(bottom));
+
+        let sample = |sx: i64, sy: i64| {
+            if sx >= 0 && sy >= 0 && sx < source_width && sy < source_height {
+                bytes[(sy * source_width + sx) as usize].premultiply()
+            } else {
+                Rgba::new(0, 0, 0, 0)
+            }
+        };
+
+        for gy in cy0..cy1 {
+            let v = gy as f32 - y;
+            let (sy, ty) = (v.floor() as i64, v - v.floor());
+
+            for gx in cx0..cx1 {
+                let u = gx as f32 - x;
+                let (sx, tx) = (u.floor() as i64, u - u.floor());
+
+                let (a, b) = (sample(sx, sy), sample(sx + 1, sy));
+                let (c, d) = (sample(sx, sy + 1), sample(sx + 1, sy + 1));
 
+                let mut color = Rgba::default();
+                for channel in Color::ALL {
+                    let top_row = a[channel] as f32 + (b[channel] as f32 - a[channel] as f32) * tx;
+                    let bottom_row = c[channel] as f32 + (d[channel] as f32 - c[channel] as f32) * tx;
+                    color[channel] = (top_row + (bottom_row - top_row) * ty).round() as u8;
+                }
+
+                let index = self.index(gx, gy);
+                self.data_mut()[index] = color.unpremultiply().over(self.data()[index].into()).into();
+            }
         }
+
+        completion(cx0 < cx1 && cy0 < cy1, cx0 > x0 || cy0 > y0 || cx1 < x1 || cy1 < y1)
     }
 
-    pub fn image_ref(&self) -> Option<ImageRef> {
-        match self {
-            ImageHandle::Image {path: _, vector, width, height } => {
-                Some(
-                    ImageRef { bytes: vector.as_slice(), width: *width, height: *height }
-                )
-            },
-            ImageHandle::Handle { path: _ } => None
+    // each row holds up to `width` source pixels, short rows are drawn as far as they go
+    fn blit_rows<'b>(&mut self, x: isize, y: isize, width: isize, rows: impl Iterator<Item = &'b [Rgba]>, mode: BlendMode) -> ImageCompletion {
+        if width == 0 {
+            return ImageCompletion::Complete;
         }
-    }
-}
 
-pub trait ColorRect<C: Into<R>, R = u32> {
-    fn get_bytes(&self) -> &[C];
-    fn get_width(&self) -> u32;
-    fn get_height(&self) -> u32;
-}
+        let (bl, bt, br, bb) = self.bounds();
+        let (bl, bt, br, bb) = (bl as isize, bt as isize, br as isize, bb as isize);
 
-pub struct Image {
-    pub bytes: Vec<Rgba>,
-    pub width: u32,
-    pub height: u32,
-}
+        // visible columns of every row, in image coordinates
+        let left = (bl - x).clamp(0, width);
+        let right = (br - x).clamp(left, width);
 
+        let (mut drawn, mut clipped) = (false, left > 0 || right < width);
 
-impl Image {
-    pub fn get_ref(&self) -> ImageRef {
-        ImageRef { bytes: self.bytes.as_slice(), width: self.width, height: self.height }
-    }
-}
+        for (row, pixels) in rows.enumerate() {
+            let gy = y + row as isize;
+            let end = right.min(pixels.len() as isize);
 
-impl ColorRect<Rgba> for Image {
-    fn get_bytes(&self) -> &[Rgba] {
-        self.bytes.as_slice()
-    }
+            if gy < bt || gy >= bb || left >= end {
+                clipped = true;
+                continue;
+            }
+            drawn = true;
 
-    fn get_width(&self) -> u32 {
-        self.width
-    }
+            let start = self.index((x + left) as i64, gy as i64);
+            let destination = &mut self.data_mut()[start..start + (end - left) as usize];
 
-    fn get_height(&self) -> u32 {
-        self.height
+            for (pixel, &source) in destination.iter_mut().zip(&pixels[left as usize..end as usize]) {
+                *pixel = mode.apply(source, (*pixel).into()).into();
+            }
+        }
+
+        completion(drawn, clipped)
     }
-}
 
-pub struct MonoImage {
-    pub bytes: Vec<u8>,
-    pub width: u32,
-    pub height: u32
-}
 
-impl ColorRect<u8, u8> for MonoImage {
-    fn get_bytes(&self) -> &[u8] {
-        self.bytes.as_slice()
+
+
+
+
+pub fn draw_monochrome_image<R: ColorRect<u8, u8>, C: Into<u32>>
+
+    (
+        &mut self,
+        x: isize, y: isize,
+        image: &R,
+        black: Rgba,
+        white: Rgba
+    ) -> ImageCompletion {
+        self.blit_mono(x, y, image, black, white, BlendMode::Replace)
     }
 
-    fn get_height(&self) -> u32 {
-        self.height
+    pub fn draw_monochrome_image_blended<R: ColorRect<u8, u8>>(&mut self, x: isize, y: isize, image: &R, black: Rgba, white: Rgba) -> ImageCompletion {
+        self.blit_mono(x, y, image, black, white, BlendMode::Over)
     }
 
-    fn get_width(&self) -> u32 {
-        self.width
+    // characters missing from the font are skipped without advancing, '\n' starts a new line
+    pub fn draw_text(&mut self, x: isize, y: isize, text: &str, font: &BitmapFont, color: Rgba) {
+        self.draw_text_aligned(x, y, 0, text, font, TextAlign::Left, color);
+    }
+
+    // each line is aligned within `max_width` of x, lines wider than that overhang it
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_text_aligned(&mut self, x: isize, y: isize, max_width: u32, text: &str, font: &BitmapFont, align: TextAlign, color: Rgba) {
+        let mut gy = y;
+
+        for line in text.split('\n') {
+            let spare = max_width as isize - font.measure(line).0 as isize;
+            let mut gx = x + match align {
+                TextAlign::Left => 0,
+                TextAlign::Center => spare / 2,
+                TextAlign::Right => spare
+            };
+
+            for ch in line.chars() {
+                gx += self.draw_char(gx, gy, ch, font, color) as isize;
+            }
+            gy += font.line_height as isize;
+        }
+    }
+
+    // returns the advance to the next character, 0 when the font has no such glyph
+    pub fn draw_char(&mut self, x: isize, y: isize, ch: char, font: &BitmapFont, color: Rgba) -> u32 {
+        match font.glyphs.get(&ch) {
+            Some(glyph) => {
+                self.draw_monochrome_image_blended(x, y, glyph, color.with_alpha(0), color);
+                glyph.width
+            },
+            None => 0
+        }
+    }
+
+    // stamps color where the mask is 255 and blends toward it by the mask value elsewhere, 0 leaves the pixel alone
+    pub fn draw_masked(&mut self, x: isize, y: isize, mask: &MonoImage, color: Rgba) {
+        if mask.width == 0 {
+            return;
+        }
+
+        for (row, gy) in mask.bytes.chunks_exact(mask.width as usize).take(mask.height as usize).zip(y as i64..) {
+            for (&coverage, gx) in row.iter().zip(x as i64..) {
+                if coverage != 0 {
+                    self.plot_blended(gx, gy, color, coverage as f32 / 255.0);
+                }
+            }
+        }
+    }
+
+    fn blit_mono<R: ColorRect<u8, u8>>(&mut self, x: isize, y: isize, image: &R, black: Rgba, white: Rgba, mode: BlendMode) -> ImageCompletion {
+        let (left, top, right, bottom) = self.bounds();
+        let (left, top, right, bottom) = (left as isize, top as isize, right as isize, bottom as isize);
+
+        let bytes = image.get_bytes();
+
+        let mut gx = x;
+        let mut gy = y;
+
+        // clipped on any side, not just past the right and bottom edges
+        let mut clipped = false;
+        let mut drawn = false;
+
+        for &byte in bytes {
+
+            if gx >= left && gy >= top && gx < right && gy < bottom {
+                let index = self.index(gx as i64, gy as i64);
+                let color = mode.apply(mono_color(byte, black, white), self.data()[index].into());
+                self.data_mut()[index] = color.into();
+                drawn = true;
+            } else {
+                clipped = true;
+            }
+
+            if gx == image.get_width() as isize + x - 1 {
+                gx = x;
+                gy += 1;
+            } else {
+                gx += 1;
+            }
+        }
+
+        completion(drawn, clipped)
+    }
+
+    // rect_width and rect_height are extents, zero or negative values draw nothing
+    pub fn draw_rectangle(&mut self, x: i64, y: i64, rect_width: i64, rect_height: i64, color: Rgba) {
+        if rect_width <= 0 || rect_height <= 0 {
+            return;
+        }
+
+        let (_, top, _, bottom) = self.bounds();
+
+        for gy in y.max(top)..(y + rect_height).min(bottom) {
+            self.fill_span(x, x + rect_width - 1, gy, color);
+        }
+    }
+
+    pub fn clear_rect(&mut self, x: i64, y: i64, w: i64, h: i64, color: Rgba) {
+        self.draw_rectangle(x, y, w, h, color);
+    }
+
+    // cells are anchored at (0, 0) in drawing coordinates, a zero cell draws nothing
+    pub fn draw_checkerboard(&mut self, cell: u32, color_a: Rgba, color_b: Rgba) {
+        if cell == 0 {
+            return;
+        }
+
+        let cell = cell as i64;
+        let (left, top, right, bottom) = self.bounds();
+
+        for gy in top..bottom {
+            let mut x = left.div_euclid(cell) * cell;
+            while x < right {
+                let color = if (x.div_euclid(cell) + gy.div_euclid(cell)) % 2 == 0 { color_a } else { color_b };
+                self.fill_span(x, x + cell - 1, gy, color);
+                x += cell;
+            }
+        }
+    }
+
+    pub fn draw_rectangle_outline(&mut self, x: i64, y: i64, w: i64, h: i64, thickness: u32, color: Rgba) {
+        if w <= 0 || h <= 0 || thickness == 0 {
+            return;
+        }
+
+        let t = thickness as i64;
+
+        if 2 * t >= w || 2 * t >= h {
+            return self.draw_rectangle(x, y, w, h, color);
+        }
+
+        self.draw_rectangle(x, y, w, t, color);
+        self.draw_rectangle(x, y + h - t, w, t, color);
+        self.draw_rectangle(x, y + t, t, h - 2 * t, color);
+        self.draw_rectangle(x + w - t, y + t, t, h - 2 * t, color);
+    }
+
+    pub fn draw_line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Rgba) {
+        if y0 == y1 {
+            return self.fill_span(x0, x1, y0, color);
+        }
+
+        if x0 == x1 {
+            let (left, top, right, bottom) = self.bounds();
+            if x0 < left || x0 >= right {
+                return;
+            }
+
+            for gy in y0.min(y1).max(top)..=y0.max(y1).min(bottom - 1) {
+                let index = self.index(x0, gy);
+                self.data_mut()[index] = color.into();
+            }
+            return;
+        }
+
+        let bounds = self.bounds();
+        bresenham(x0, y0, x1, y1, bounds, |gx, gy, _| self.plot(gx, gy, color));
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_dashed_line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, on: u32, off: u32, color: Rgba) {
+        if off == 0 {
+            return self.draw_line(x0, y0, x1, y1, color);
+        }
+
+        let period = on as u64 + off as u64;
+        let bounds = self.bounds();
+
+        bresenham(x0, y0, x1, y1, bounds, |gx, gy, step| {
+            if step % period < on as u64 {
+                self.plot(gx, gy, color);
+            }
+        });
+    }
+
+    pub fn draw_thick_line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, width: u32, color: Rgba) {
+        match width {
+            0 => return,
+            1 => return self.draw_line(x0, y0, x1, y1, color),
+            _ => {}
+        }
+
+        let (left, top, right, bottom) = self.bounds();
+
+        let half = width as f64 / 2.0;
+        let (dx, dy) = ((x1 - x0) as f64, (y1 - y0) as f64);
+        let length = (dx * dx + dy * dy).sqrt();
+        let (ux, uy) = if length == 0.0 { (1.0, 0.0) } else { (dx / length, dy / length) };
+
+        // square caps reach at most half * sqrt(2) past either endpoint
+        let reach = (half * std::f64::consts::SQRT_2).ceil() as i64;
+
+        let (min_x, max_x) = ((x0.min(x1) - reach).max(left), (x0.max(x1) + reach).min(right - 1));
+        let (min_y, max_y) = ((y0.min(y1) - reach).max(top), (y0.max(y1) + reach).min(bottom - 1));
+
+        for gy in min_y..=max_y {
+            for gx in min_x..=max_x {
+                let (px, py) = ((gx - x0) as f64, (gy - y0) as f64);
+
+                let along = px * ux + py * uy;
+                let across = py * ux - px * uy;
+
+                if along >= -half && along < length + half && across >= -half && across < half {
+                    let index = self.index(gx, gy);
+                    self.data_mut()[index] = color.into();
+                }
+            }
+        }
+    }
+
+    pub fn draw_line_aa(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: Rgba) {
+        // clipped with a margin so the endpoint fade only ever lands off the canvas
+        let (left, top, right, bottom) = self.bounds();
+        let margin = (left as f64 - 2.0, top as f64 - 2.0, right as f64 + 2.0, bottom as f64 + 2.0);
+        let Some((x0, y0, x1, y1)) = clip_segment(x0 as f64, y0 as f64, x1 as f64, y1 as f64, margin) else {
+            return;
+        };
+
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+
+        let (mut x0, mut y0, mut x1, mut y1) = if steep {
+            (y0, x0, y1, x1)
+        } else {
+            (x0, y0, x1, y1)
+        };
+
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+
+        // first endpoint
+        let xend = (x0 + 0.5).floor();
+        let yend = y0 + gradient * (xend - x0);
+        let xgap = 1.0 - fpart(x0 + 0.5);
+        let xpxl1 = xend as i64;
+        let ypxl1 = yend.floor() as i64;
+
+        self.plot_wu(xpxl1, ypxl1, steep, color, (1.0 - fpart(yend)) * xgap);
+        self.plot_wu(xpxl1, ypxl1 + 1, steep, color, fpart(yend) * xgap);
+
+        let mut intery = yend + gradient;
+
+        // second endpoint
+        let xend = (x1 + 0.5).floor();
+        let yend = y1 + gradient * (xend - x1);
+        let xgap = fpart(x1 + 0.5);
+        let xpxl2 = xend as i64;
+        let ypxl2 = yend.floor() as i64;
+
+        self.plot_wu(xpxl2, ypxl2, steep, color, (1.0 - fpart(yend)) * xgap);
+        self.plot_wu(xpxl2, ypxl2 + 1, steep, color, fpart(yend) * xgap);
+
+        for gx in (xpxl1 + 1)..xpxl2 {
+            let gy = intery.floor() as i64;
+
+            self.plot_wu(gx, gy, steep, color, 1.0 - fpart(intery));
+            self.plot_wu(gx, gy + 1, steep, color, fpart(intery));
+
+            intery += gradient;
+        }
+    }
+
+    pub fn draw_circle(&mut self, cx: i64, cy: i64, radius: u32, color: Rgba) {
+        let mut x = radius as i64;
+        let mut y = 0;
+        let mut err = 1 - x;
+
+        while x >= y {
+            for (px, py) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                self.plot(cx + px, cy + py, color);
+            }
+
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    pub fn fill_circle(&mut self, cx: i64, cy: i64, radius: u32, color: Rgba) {
+        let (_, top, _, bottom) = self.bounds();
+        let r = radius as i128;
+
+        // only the rows on the canvas, and r * r only fits in i128 near u32::MAX
+        let first = (top as i128 - cy as i128).max(-r);
+        let last = (bottom as i128 - 1 - cy as i128).min(r);
+
+        for dy in first..=last {
+            let half = (r * r - dy * dy).isqrt() as i64;
+            self.fill_span(cx.saturating_sub(half), cx.saturating_add(half), (cy as i128 + dy) as i64, color);
+        }
+    }
+
+    // radii are capped at 2^30 so the decision terms fit in i128, far past any real canvas
+    pub fn draw_ellipse(&mut self, cx: i64, cy: i64, rx: u32, ry: u32, color: Rgba) {
+        let (a, b) = (rx.min(1 << 30) as i128, ry.min(1 << 30) as i128);
+
+        if a == 0 || b == 0 {
+            let (a, b) = (a as i64, b as i64);
+            return self.draw_line(cx - a, cy - b, cx + a, cy + b, color);
+        }
+
+        let (a2, b2) = (a * a, b * b);
+        let mut x = 0;
+        let mut y = b;
+
+        // decision variables are kept at 4x scale to stay in integer math
+        let mut dx = 0;
+        let mut dy = 2 * a2 * y;
+        let mut d1 = 4 * b2 - 4 * a2 * b + a2;
+
+        while dx < dy {
+            self.plot_ellipse_points(cx, cy, x as i64, y as i64, color);
+
+            x += 1;
+            dx += 2 * b2;
+            if d1 < 0 {
+                d1 += 4 * (dx + b2);
+            } else {
+                y -= 1;
+                dy -= 2 * a2;
+                d1 += 4 * (dx - dy + b2);
+            }
+        }
+
+        let mut d2 = b2 * (2 * x + 1) * (2 * x + 1) + 4 * a2 * (y - 1) * (y - 1) - 4 * a2 * b2;
+
+        while y >= 0 {
+            self.plot_ellipse_points(cx, cy, x as i64, y as i64, color);
+
+            y -= 1;
+            dy -= 2 * a2;
+            if d2 > 0 {
+                d2 += 4 * (a2 - dy);
+            } else {
+                x += 1;
+                dx += 2 * b2;
+                d2 += 4 * (dx - dy + a2);
+            }
+        }
+    }
+
+    fn plot_ellipse_points(&mut self, cx: i64, cy: i64, x: i64, y: i64, color: Rgba) {
+        self.plot(cx + x, cy + y, color);
+        self.plot(cx - x, cy + y, color);
+        self.plot(cx + x, cy - y, color);
+        self.plot(cx - x, cy - y, color);
+    }
+
+    pub fn fill_ellipse(&mut self, cx: i64, cy: i64, rx: u32, ry: u32, color: Rgba) {
+        let (a, b) = (rx as i64, ry as i64);
+
+        if b == 0 {
+            return self.fill_span(cx - a, cx + a, cy, color);
+        }
+
+        let (_, top, _, bottom) = self.bounds();
+        let (a2, b2) = ((a as u128).pow(2), (b as u128).pow(2));
+
+        // only the rows on the canvas, a2 * (b2 - dy * dy) needs u128 for large radii
+        let first = (top as i128 - cy as i128).max(-b as i128);
+        let last = (bottom as i128 - 1 - cy as i128).min(b as i128);
+
+        for dy in first..=last {
+            let half = (a2 * (b2 - dy.unsigned_abs().pow(2)) / b2).isqrt() as i64;
+            self.fill_span(cx.saturating_sub(half), cx.saturating_add(half), (cy as i128 + dy) as i64, color);
+        }
+    }
+
+    pub fn fill_rounded_rectangle(&mut self, x: i64, y: i64, w: i64, h: i64, radius: u32, color: Rgba) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+
+        let r = (radius as i64).min(w / 2).min(h / 2);
+        let (_, top, _, bottom) = self.bounds();
+
+        for gy in y.max(top)..(y + h).min(bottom) {
+            let dy = if gy < y + r {
+                y + r - gy
+            } else if gy > y + h - 1 - r {
+                gy - (y + h - 1 - r)
+            } else {
+                0
+            };
+
+            let inset = r - (r * r - dy * dy).isqrt();
+            self.fill_span(x + inset, x + w - 1 - inset, gy, color);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn fill_gradient_linear(&mut self, x: i64, y: i64, w: i64, h: i64, start: Rgba, end: Rgba, vertical: bool) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+
+        let (left, top, right, bottom) = self.bounds();
+        let (steps, offset, low, high) = if vertical { (h, y, top, bottom) } else { (w, x, left, right) };
+
+        for i in (low - offset).max(0)..steps.min(high - offset) {
+            let proportion = if steps == 1 { 0 } else { (i * 255 / (steps - 1)) as u8 };
+            let color = start.blend(end, proportion);
+
+            if vertical {
+                self.fill_span(x, x + w - 1, y + i, color);
+            } else {
+                self.draw_line(x + i, y, x + i, y + h - 1, color);
+            }
+        }
+    }
+
+    pub fn fill_gradient_radial(&mut self, cx: i64, cy: i64, radius: u32, inner: Rgba, outer: Rgba) {
+        let (left, top, right, bottom) = self.bounds();
+        let r = radius as i128;
+
+        // distances are taken in i128 so far-off centres and huge radii cannot overflow
+        let rows = cy.saturating_sub(radius as i64).max(top)..=cy.saturating_add(radius as i64).min(bottom - 1);
+        let columns = cx.saturating_sub(radius as i64).max(left)..=cx.saturating_add(radius as i64).min(right - 1);
+
+        for gy in rows {
+            for gx in columns.clone() {
+                let distance_squared = (gx as i128 - cx as i128).pow(2) + (gy as i128 - cy as i128).pow(2);
+
+                if distance_squared > r * r {
+                    continue;
+                }
+
+                let proportion = if r == 0 {
+                    0
+                } else {
+                    ((distance_squared as f64).sqrt() * 255.0 / r as f64).round() as u8
+                };
+
+                let index = self.index(gx, gy);
+                self.data_mut()[index] = inner.blend(outer, proportion).into();
+            }
+        }
+    }
+
+    pub fn fill_polygon(&mut self, points: &[(i64, i64)], color: Rgba) {
+        match points {
+            [] | [_] => return,
+            [(x0, y0), (x1, y1)] => return self.draw_line(*x0, *y0, *x1, *y1, color),
+            _ => {}
+        }
+
+        let (_, top, _, bottom) = self.bounds();
+
+        let min_y = points.iter().map(|p| p.1).min().unwrap_or(0).max(top);
+        let max_y = points.iter().map(|p| p.1).max().unwrap_or(0).min(bottom - 1);
+
+        let mut crossings: Vec<f64> = Vec::new();
+
+        for gy in min_y..=max_y {
+            crossings.clear();
+
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+
+                // half-open so shared vertices are only counted once
+                if (y0 <= gy && gy < y1) || (y1 <= gy && gy < y0) {
+                    let t = (gy - y0) as f64 / (y1 - y0) as f64;
+                    crossings.push(x0 as f64 + t * (x1 - x0) as f64);
+                }
+            }
+
+            crossings.sort_by(|a, b| a.total_cmp(b));
+
+            for pair in crossings.chunks_exact(2) {
+                let (start, end) = (pair[0].ceil() as i64, pair[1].ceil() as i64 - 1);
+
+                if start <= end {
+                    self.fill_span(start, end, gy, color);
+                }
+            }
+        }
+    }
+
+    pub fn draw_polyline(&mut self, points: &[(i64, i64)], closed: bool, color: Rgba) {
+        if points.len() < 2 {
+            return;
+        }
+
+        for pair in points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            self.draw_line(x0, y0, x1, y1, color);
+        }
+
+        if closed {
+            let ((x0, y0), (x1, y1)) = (points[points.len() - 1], points[0]);
+            self.draw_line(x0, y0, x1, y1, color);
+        }
+    }
+
+    pub fn draw_quadratic_bezier(&mut self, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), color: Rgba) {
+        let c1 = (p0.0 + (p1.0 - p0.0) * 2.0 / 3.0, p0.1 + (p1.1 - p0.1) * 2.0 / 3.0);
+        let c2 = (p2.0 + (p1.0 - p2.0) * 2.0 / 3.0, p2.1 + (p1.1 - p2.1) * 2.0 / 3.0);
+
+        self.draw_cubic_bezier(p0, c1, c2, p2, color);
+    }
+
+    pub fn draw_cubic_bezier(&mut self, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), color: Rgba) {
+        let mut points = vec![p0];
+        flatten_cubic(p0, p1, p2, p3, 0, &mut points);
+
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            self.draw_line(a.0.round() as i64, a.1.round() as i64, b.0.round() as i64, b.1.round() as i64, color);
+        }
+    }
+
+    fn fill_span(&mut self, x0: i64, x1: i64, y: i64, color: Rgba) {
+        let (left, top, right, bottom) = self.bounds();
+
+        if y < top || y >= bottom {
+            return;
+        }
+
+        let start = x0.min(x1).max(left);
+        let end = x0.max(x1).min(right - 1);
+
+        if start <= end {
+            let (first, last) = (self.index(start, y), self.index(end, y));
+            self.data_mut()[first..=last].fill(color.into());
+        }
+    }
+
+    fn plot_wu(&mut self, x: i64, y: i64, steep: bool, color: Rgba, coverage: f32) {
+        if steep {
+            self.plot_blended(y, x, color, coverage);
+        } else {
+            self.plot_blended(x, y, color, coverage);
+        }
+    }
+
+    fn plot_blended(&mut self, x: i64, y: i64, color: Rgba, coverage: f32) {
+        let (left, top, right, bottom) = self.bounds();
+
+        if x >= left && y >= top && x < right && y < bottom {
+            let index = self.index(x, y);
+            let proportion = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+            self.data_mut()[index] = Rgba::from(self.data()[index]).blend(color, proportion).into();
+        }
+    }
+
+    fn plot(&mut self, x: i64, y: i64, color: Rgba) {
+        let (left, top, right, bottom) = self.bounds();
+
+        if x >= left && y >= top && x < right && y < bottom {
+            let index = self.index(x, y);
+            self.data_mut()[index] = color.into();
+        }
+    }
+
+    // drawable area in drawing coordinates as (left, top, right, bottom), exclusive on the right and bottom
+    fn bounds(&self) -> (i64, i64, i64, i64) {
+        let (wx, wy) = (self.width.get() as i64, self.height.get() as i64);
+        let (ox, oy) = self.origin;
+
+        let (left, top, right, bottom) = match self.clip {
+            None => (0, 0, wx, wy),
+            Some((x, y, w, h)) => (x.max(0), y.max(0), (x + w).min(wx), (y + h).min(wy))
+        };
+
+        (left - ox, top - oy, right - ox, bottom - oy)
+    }
+
+    fn into_pixels(mut self) -> Pixels<'a, D, W> {
+        self.present_downsampled();
+        self.buffer
+    }
+
+    fn set_supersampling(&mut self, width: NonZeroU32, height: NonZeroU32, factor: u32) {
+        if factor <= 1 {
+            self.supersample = None;
+            return;
+        }
+
+        let scale = NonZeroU32::new(factor).unwrap();
+        self.width = width.saturating_mul(scale);
+        self.height = height.saturating_mul(scale);
+        self.supersample = Some((vec![0; self.width.get() as usize * self.height.get() as usize], factor));
+    }
+
+    // the pixels draw calls write to, the real buffer unless supersampling
+    fn data(&self) -> &[u32] {
+        match &self.supersample {
+            Some((samples, _)) => samples,
+            None => &self.buffer
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut [u32] {
+        match &mut self.supersample {
+            Some((samples, _)) => samples,
+            None => &mut self.buffer
+        }
+    }
+
+    fn index(&self, x: i64, y: i64) -> usize {
+        ((y + self.origin.1) * self.width.get() as i64 + x + self.origin.0) as usize
     }
 }
 
-pub struct ImageRef<'a> {
-    bytes: &'a [Rgba],
-    width: u32,
-    height: u32
+// liang-barsky, None for a segment entirely outside the rect or with non-finite ends
+fn clip_segment(x0: f64, y0: f64, x1: f64, y1: f64, rect: (f64, f64, f64, f64)) -> Option<(f32, f32, f32, f32)> {
+    if ![x0, y0, x1, y1].iter().all(|v| v.is_finite()) {
+        return None;
+    }
+
+    let (left, top, right, bottom) = rect;
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    let (mut start, mut end) = ((x0, y0), (x1, y1));
+
+    // clipped ends are placed on the edge they cross, rather than at x0 + t * dx,
+    // which keeps them precise when the other end is very far away
+    let on_x = |x: f64| (x, y0 + (x - x0) * (dy / dx));
+    let on_y = |y: f64| (x0 + (y - y0) * (dx / dy), y);
+
+    for (p, q, edge) in [(-dx, x0 - left, on_x(left)), (dx, right - x0, on_x(right)), (-dy, y0 - top, on_y(top)), (dy, bottom - y0, on_y(bottom))] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 && q / p > t0 {
+            (t0, start) = (q / p, edge);
+        } else if p > 0.0 && q / p < t1 {
+            (t1, end) = (q / p, edge);
+        }
+    }
+
+    if t0 > t1 {
+        return None;
+    }
+
+    Some((start.0 as f32, start.1 as f32, end.0 as f32, end.1 as f32))
+}
+
+// walks only the steps of the line that land inside bounds, passing each point with its step
+// index along the whole line, so clipped dashes keep their phase
+fn bresenham(x0: i64, y0: i64, x1: i64, y1: i64, bounds: (i64, i64, i64, i64), mut plot: impl FnMut(i64, i64, u64)) {
+    let (left, top, right, bottom) = bounds;
+    let (dx, dy) = (x1 as i128 - x0 as i128, y1 as i128 - y0 as i128);
+
+    // a is the major axis, b the minor one
+    let ((a0, da, a_min, a_max), (b0, db, b_min, b_max)) = if dx.abs() >= dy.abs() {
+        ((x0, dx, left, right), (y0, dy, top, bottom))
+    } else {
+        ((y0, dy, top, bottom), (x0, dx, left, right))
+    };
+    let (a0, b0) = (a0 as i128, b0 as i128);
+    let (sa, sb) = (da.signum(), db.signum());
+    let (len, rise) = (da.unsigned_abs(), db.unsigned_abs());
+
+    if len == 0 {
+        if x0 >= left && x0 < right && y0 >= top && y0 < bottom {
+            plot(x0, y0, 0);
+        }
+        return;
+    }
+
+    // the minor offset after k steps, rounded half up
+    let offset = |k: u128| {
+        let p = k * rise;
+        p / len + (2 * (p % len) >= len) as u128
+    };
+
+    // steps whose major coordinate is inside bounds
+    let (a_lo, a_hi) = if sa > 0 { (a_min as i128 - a0, a_max as i128 - 1 - a0) } else { (a0 - (a_max as i128 - 1), a0 - a_min as i128) };
+    let (b_lo, b_hi) = if sb >= 0 { (b_min as i128 - b0, b_max as i128 - 1 - b0) } else { (b0 - (b_max as i128 - 1), b0 - b_min as i128) };
+
+    if a_hi < 0 || a_lo > len as i128 || b_hi < 0 || b_lo > rise as i128 {
+        return;
+    }
+    let (mut first, mut last) = (a_lo.max(0) as u128, (a_hi as u128).min(len));
+    let (b_lo, b_hi) = (b_lo.max(0) as u128, b_hi as u128);
+
+    // the offset never decreases, so narrow to the steps whose minor coordinate is inside bounds too
+    let (mut lo, mut hi) = (first, last + 1);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if offset(mid) < b_lo { lo = mid + 1 } else { hi = mid }
+    }
+    first = lo;
+
+    let (mut lo, mut hi) = (first, last + 1);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if offset(mid) <= b_hi { lo = mid + 1 } else { hi = mid }
+    }
+    if lo == first {
+        return;
+    }
+    last = lo - 1;
+
+    let (mut whole, mut rest) = ((first * rise) / len, (first * rise) % len);
+    for k in first..=last {
+        let b = whole + (2 * rest >= len) as u128;
+        let (a, b) = ((a0 + sa * k as i128) as i64, (b0 + sb * b as i128) as i64);
+
+        if dx.abs() >= dy.abs() {
+            plot(a, b, k as u64);
+        } else {
+            plot(b, a, k as u64);
+        }
+
+        rest += rise;
+        if rest >= len {
+            whole += 1;
+            rest -= len;
+        }
+    }
+}
+
+fn flatten_cubic(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), depth: u32, out: &mut Vec<(f32, f32)>) {
+    let flat = segment_distance(p1, p0, p3).max(segment_distance(p2, p0, p3)) < 0.5;
+
+    if flat || depth >= 16 {
+        out.push(p3);
+        return;
+    }
+
+    let mid = |a: (f32, f32), b: (f32, f32)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+
+    let (p01, p12, p23) = (mid(p0, p1), mid(p1, p2), mid(p2, p3));
+    let (p012, p123) = (mid(p01, p12), mid(p12, p23));
+    let split = mid(p012, p123);
+
+    flatten_cubic(p0, p01, p012, split, depth + 1, out);
+    flatten_cubic(split, p123, p23, p3, depth + 1, out);
+}
+
+fn segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+
+    let (nx, ny) = (a.0 + t * dx - p.0, a.1 + t * dy - p.1);
+    (nx * nx + ny * ny).sqrt()
+}
+
+fn fpart(x: f32) -> f32 {
+    x - x.floor()
+}
+
+// nothing clipped away counts as complete, even when there was nothing to draw
+fn completion(drawn: bool, clipped: bool) -> ImageCompletion {
+    match (drawn, clipped) {
+        (false, true) => ImageCompletion::None,
+        (true, true) => ImageCompletion::Partial,
+        _ => ImageCompletion::Complete
+    }
+}
+
+pub enum ImageHandle {
+    Handle {
+        path: &'static str
+    },
+    Image {
+        path: &'static str,
+        vector: Vec<Rgba>,
+        width: u32,
+        height: u32
+    }
+}
+
+impl ImageHandle {
+
+    pub fn load(&mut self) -> Result<(), ImageError> {
+        use ImageHandle::*;
+
+        match self {
+            Handle {
+                path
+            } => {
+
+                let file = File::open(*path)?;
+                let image = crate::canvas::Image::from_reader(file)?;
+
+                *self = ImageHandle::Image { path, vector: image.bytes, width: image.width, height: image.height };
+
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    pub fn unload(&mut self) {
+        match self {
+            ImageHandle::Handle { path } | ImageHandle::Image { path, vector: _, width: _, height: _ } => {
+                *self = ImageHandle::Handle { path };
+            }
+        }
+    }
+
+    pub fn to_image(self) -> Option<Image> {
+        match self {
+            ImageHandle::Image { path: _, vector, width, height } => {
+                Some(Image {
+                    bytes: vector.clone(),
+                    width,
+                    height
+                })
+            },
+            ImageHandle::Handle { path: _ } => None
+
+        }
+    }
+
+    pub fn image_ref(&self) -> Option<ImageRef<'_>> {
+        match self {
+            ImageHandle::Image {path: _, vector, width, height } => {
+                Some(
+                    ImageRef { bytes: vector.as_slice(), width: *width, height: *height }
+                )
+            },
+            ImageHandle::Handle { path: _ } => None
+        }
+    }
+}
+
+// picks the decoder from the file's magic bytes rather than trusting the extension
+fn decode_any<R: BufRead + Seek>(mut reader: R) -> Result<(Vec<Rgba>, u32, u32), ImageError> {
+    // a png signature is the longest one sniffed here, a single read may return fewer bytes than that
+    let start = reader.stream_position()?;
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature)?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    match image::guess_format(&signature)? {
+        ImageFormat::Png => decode_rgba(PngDecoder::new(reader)?),
+        ImageFormat::Jpeg => decode_rgba(JpegDecoder::new(reader)?),
+        other => Err(ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+            other.into(),
+            UnsupportedErrorKind::Format(other.into())
+        )))
+    }
+}
+
+// reads any 8 or 16 bit gray, gray-alpha, RGB or RGBA source into Rgba, narrowing 16 bit samples
+fn decode_rgba(decoder: impl ImageDecoder) -> Result<(Vec<Rgba>, u32, u32), ImageError> {
+    use image::ColorType::*;
+
+    let color_type = decoder.color_type();
+    let (width, height) = decoder.dimensions();
+
+    let mut buf: Vec<u8> = vec!(0; decoder.total_bytes() as usize);
+    decoder.read_image(buf.as_bytes_mut())?;
+
+    let samples: Vec<u8> = match color_type {
+        L8 | La8 | Rgb8 | Rgba8 => buf,
+        L16 | La16 | Rgb16 | Rgba16 => buf
+            .chunks_exact(2)
+            .map(|sample| ((u16::from_ne_bytes([sample[0], sample[1]]) as u32 + 128) / 257) as u8)
+            .collect(),
+        other => {
+            return Err(ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+                ImageFormatHint::Unknown,
+                UnsupportedErrorKind::Color(other.into())
+            )));
+        }
+    };
+
+    let vector = samples
+        .chunks_exact(color_type.channel_count() as usize)
+        .map(|pixel| match *pixel {
+            [l] => Rgba::new_opaque(l, l, l),
+            [l, a] => Rgba::new(l, l, l, a),
+            [r, g, b] => Rgba::new_opaque(r, g, b),
+            [r, g, b, a] => Rgba::new(r, g, b, a),
+            _ => unreachable!()
+        })
+        .collect();
+
+    Ok((vector, width, height))
+}
+
+pub trait ColorRect<C: Into<R>, R = u32> {
+    fn get_bytes(&self) -> &[C];
+    fn get_width(&self) -> u32;
+    fn get_height(&self) -> u32;
+}
+
+#[derive(Clone)]
+pub struct Image {
+    pub bytes: Vec<Rgba>,
+    pub width: u32,
+    pub height: u32,
+}
+
+
+impl Image {
+
+    pub fn from_raw(width: u32, height: u32, bytes: Vec<Rgba>) -> Result<Image, ImageDimError> {
+        let expected = width as usize * height as usize;
+
+        if bytes.len() != expected {
+            return Err(ImageDimError::LengthMismatch(expected, bytes.len()));
+        }
+
+        Ok(Image { bytes, width, height })
+    }
+
+    pub fn new_filled(width: u32, height: u32, color: Rgba) -> Image {
+        Image {
+            bytes: vec![color; width as usize * height as usize],
+            width,
+            height
+        }
+    }
+
+    pub fn get_ref(&self) -> ImageRef<'_> {
+        ImageRef { bytes: self.bytes.as_slice(), width: self.width, height: self.height }
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), ImageError> {
+        let file = BufWriter::new(File::create(path)?);
+        let bytes: Vec<u8> = self.bytes.iter().flat_map(|&pixel| <[u8; 4]>::from(pixel)).collect();
+
+        PngEncoder::new(file).write_image(&bytes, self.width, self.height, ExtendedColorType::Rgba8)
+    }
+
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Image, ImageError> {
+        let (bytes, width, height) = decode_any(BufReader::new(reader))?;
+        Ok(Image { bytes, width, height })
+    }
+
+    pub fn from_png_bytes(data: &[u8]) -> Result<Image, ImageError> {
+        let (bytes, width, height) = decode_rgba(PngDecoder::new(Cursor::new(data))?)?;
+        Ok(Image { bytes, width, height })
+    }
+
+    // the image crate stores [r, g, b, a], Rgba stores [b, g, r, a]
+    pub fn from_image_rgba(img: &image::RgbaImage) -> Image {
+        let bytes = img.pixels().map(|&image::Rgba([r, g, b, a])| Rgba::new(r, g, b, a)).collect();
+        Image { bytes, width: img.width(), height: img.height() }
+    }
+
+    pub fn from_dynamic(img: image::DynamicImage) -> Image {
+        Image::from_image_rgba(&img.to_rgba8())
+    }
+
+    pub fn to_image_rgba(&self) -> image::RgbaImage {
+        image::RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let pixel = self.get_pixel(x, y).unwrap_or_default();
+            image::Rgba([pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()])
+        })
+    }
+
+    pub fn crop(&self, x: u32, y: u32, w: u32, h: u32) -> Option<Image> {
+        self.get_ref().crop(x, y, w, h)
+    }
+
+    pub fn trim_transparent(&self) -> Option<Image> {
+        let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+
+        for (i, pixel) in self.bytes.iter().enumerate() {
+            if pixel.alpha() == 0 {
+                continue;
+            }
+
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+
+        if left == u32::MAX {
+            return None;
+        }
+
+        self.crop(left, top, right - left + 1, bottom - top + 1)
+    }
+
+    // any remainder past the last full cell is dropped
+    pub fn split_grid(&self, cols: u32, rows: u32) -> Vec<Image> {
+        if cols == 0 || rows == 0 {
+            return Vec::new();
+        }
+
+        let (cell_w, cell_h) = (self.width / cols, self.height / rows);
+        if cell_w == 0 || cell_h == 0 {
+            return Vec::new();
+        }
+
+        let mut frames = Vec::with_capacity(cols as usize * rows as usize);
+        for row in 0..rows {
+            for col in 0..cols {
+                frames.extend(self.crop(col * cell_w, row * cell_h, cell_w, cell_h));
+            }
+        }
+        frames
+    }
+
+    pub fn concat_horizontal(images: &[ImageRef]) -> Option<Image> {
+        let height = images.first()?.height;
+        if images.iter().any(|image| image.height != height) {
+            return None;
+        }
+
+        let width = images.iter().map(|image| image.width).sum();
+        let mut bytes = Vec::with_capacity(width as usize * height as usize);
+        for row in 0..height as usize {
+            for image in images {
+                let start = row * image.width as usize;
+                bytes.extend_from_slice(&image.bytes[start..start + image.width as usize]);
+            }
+        }
+
+        Some(Image { bytes, width, height })
+    }
+
+    pub fn concat_vertical(images: &[ImageRef]) -> Option<Image> {
+        let width = images.first()?.width;
+        if images.iter().any(|image| image.width != width) {
+            return None;
+        }
+
+        let height = images.iter().map(|image| image.height).sum();
+        let bytes = images.iter().flat_map(|image| image.bytes.iter().copied()).collect();
+
+        Some(Image { bytes, width, height })
+    }
+
+    pub fn composite(&mut self, x: i32, y: i32, src: &ImageRef) {
+        let (x, y) = (x as i64, y as i64);
+
+        // overlap of src with self, in src coordinates
+        let left = (-x).max(0);
+        let top = (-y).max(0);
+        let right = (self.width as i64 - x).min(src.width as i64);
+        let bottom = (self.height as i64 - y).min(src.height as i64);
+
+        for sy in top..bottom {
+            for sx in left..right {
+                let index = ((sy + y) * self.width as i64 + sx + x) as usize;
+                let source = src.bytes[(sy * src.width as i64 + sx) as usize];
+                self.bytes[index] = source.over(self.bytes[index]);
+            }
+        }
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        self.get_ref().get_pixel(x, y)
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Rgba) {
+        if x < self.width && y < self.height {
+            self.bytes[y as usize * self.width as usize + x as usize] = color;
+        }
+    }
+
+    // every item is exactly `width` long, a zero width image has no rows
+    pub fn rows(&self) -> impl Iterator<Item = &[Rgba]> {
+        self.bytes.chunks_exact(self.width.max(1) as usize).take(self.height as usize)
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Rgba]> {
+        self.bytes.chunks_exact_mut(self.width.max(1) as usize).take(self.height as usize)
+    }
+
+    pub fn resize_nearest(&self, new_w: u32, new_h: u32) -> Image {
+        // keeps the requested size even when there is nothing to sample, an empty source gives transparent pixels
+        if new_w == 0 || new_h == 0 || self.width == 0 || self.height == 0 {
+            return Image::new_filled(new_w, new_h, Rgba::new(0, 0, 0, 0));
+        }
+
+        // sample at the center of each destination pixel
+        let source = |d: u32, new: u32, old: u32| ((2 * d as u64 + 1) * old as u64 / (2 * new as u64)) as usize;
+
+        let mut bytes = Vec::with_capacity(new_w as usize * new_h as usize);
+        for y in 0..new_h {
+            let row = source(y, new_h, self.height) * self.width as usize;
+            for x in 0..new_w {
+                bytes.push(self.bytes[row + source(x, new_w, self.width)]);
+            }
+        }
+
+        Image { bytes, width: new_w, height: new_h }
+    }
+
+    pub fn resize_bilinear(&self, new_w: u32, new_h: u32) -> Image {
+        if new_w == 0 || new_h == 0 || self.width == 0 || self.height == 0 {
+            return Image::new_filled(new_w, new_h, Rgba::new(0, 0, 0, 0));
+        }
+
+        // returns the two texels to either side of the sample and the weight of the second
+        let source = |d: u32, new: u32, old: u32| {
+            let s = ((d as f32 + 0.5) * old as f32 / new as f32 - 0.5).clamp(0.0, (old - 1) as f32);
+            let low = s as u32;
+            (low as usize, (low + 1).min(old - 1) as usize, s - low as f32)
+        };
+
+        let width = self.width as usize;
+        let mut bytes = Vec::with_capacity(new_w as usize * new_h as usize);
+        for y in 0..new_h {
+            let (y0, y1, fy) = source(y, new_h, self.height);
+            for x in 0..new_w {
+                let (x0, x1, fx) = source(x, new_w, self.width);
+
+                let top = self.bytes[y0 * width + x0].lerp(self.bytes[y0 * width + x1], fx);
+                let bottom = self.bytes[y1 * width + x0].lerp(self.bytes[y1 * width + x1], fx);
+                bytes.push(top.lerp(bottom, fy));
+            }
+        }
+
+        Image { bytes, width: new_w, height: new_h }
+    }
+
+    pub fn flip_horizontal(&self) -> Image {
+        let mut bytes = self.bytes.clone();
+        if self.width > 0 {
+            for row in bytes.chunks_exact_mut(self.width as usize) {
+                row.reverse();
+            }
+        }
+
+        Image { bytes, width: self.width, height: self.height }
+    }
+
+    pub fn flip_vertical(&self) -> Image {
+        let mut bytes = Vec::with_capacity(self.bytes.len());
+        if self.width > 0 {
+            for row in self.bytes.chunks_exact(self.width as usize).rev() {
+                bytes.extend_from_slice(row);
+            }
+        }
+
+        Image { bytes, width: self.width, height: self.height }
+    }
+
+    // clockwise, the result is height wide and width tall
+    pub fn rotate_90(&self) -> Image {
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut bytes = Vec::with_capacity(w * h);
+
+        for ny in 0..w {
+            for nx in 0..h {
+                bytes.push(self.bytes.get((h - 1 - nx) * w + ny).copied().unwrap_or_default());
+            }
+        }
+
+        Image { bytes, width: self.height, height: self.width }
+    }
+
+    pub fn convolve(&self, kernel: &[f32], kw: u32, kh: u32) -> Result<Image, ImageDimError> {
+        let expected = kw as usize * kh as usize;
+
+        if kernel.len() != expected {
+            return Err(ImageDimError::KernelMismatch(expected, kernel.len()));
+        }
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (anchor_x, anchor_y) = ((kw / 2) as i64, (kh / 2) as i64);
+
+        let mut bytes = Vec::with_capacity(self.bytes.len());
+        for y in 0..height {
+            for x in 0..width {
+
+                let mut sum = [0.0f32; 4];
+                for (k, &weight) in kernel.iter().enumerate() {
+                    let sx = (x + (k as i64 % kw as i64) - anchor_x).clamp(0, width - 1);
+                    let sy = (y + (k as i64 / kw as i64) - anchor_y).clamp(0, height - 1);
+
+                    let texel = self.bytes[(sy * width + sx) as usize];
+                    for (i, channel) in sum.iter_mut().enumerate() {
+                        *channel += texel[i] as f32 * weight;
+                    }
+                }
+
+                let mut out = Rgba::default();
+                for (i, channel) in sum.into_iter().enumerate() {
+                    out[i] = channel.round().clamp(0.0, 255.0) as u8;
+                }
+                bytes.push(out);
+            }
+        }
+
+        Ok(Image { bytes, width: self.width, height: self.height })
+    }
+
+    pub fn box_blur(&self, radius: u32) -> Image {
+        // like gaussian_blur, taps past the image size would only repeat the border
+        let radius = radius.min(self.width.max(self.height).max(1));
+        let size = 2 * radius as usize + 1;
+        self.convolve_separable(&vec![1.0 / size as f32; size])
+    }
+
+    pub fn gaussian_blur(&self, sigma: f32) -> Image {
+        if sigma.is_nan() || sigma <= 0.0 {
+            return self.convolve_separable(&[1.0]);
+        }
+
+        // edges are clamped, so taps beyond the image size only repeat the border
+        let limit = self.width.max(self.height).max(1) as f32;
+        let radius = (sigma * 3.0).ceil().min(limit) as i32;
+        let mut kernel: Vec<f32> = (-radius..=radius)
+            .map(|x| x as f32)
+            .map(|x| (-(x * x) / (2.0 * sigma * sigma)).exp())
+            .collect();
+
+        let total: f32 = kernel.iter().sum();
+        kernel.iter_mut().for_each(|weight| *weight /= total);
+
+        self.convolve_separable(&kernel)
+    }
+
+    fn convolve_separable(&self, kernel: &[f32]) -> Image {
+        if kernel.len() == 1 {
+            return Image { bytes: self.bytes.clone(), width: self.width, height: self.height };
+        }
+
+        // both passes are sized from the kernel itself, so neither can mismatch
+        let n = kernel.len() as u32;
+        let horizontal = self.convolve(kernel, n, 1).unwrap();
+        horizontal.convolve(kernel, 1, n).unwrap()
+    }
+
+    pub fn adjust_brightness(&self, delta: i16) -> Image {
+        self.map_rgb(|_, channel| (channel as i32 + delta as i32).clamp(0, 255) as u8)
+    }
+
+    pub fn adjust_contrast(&self, factor: f32) -> Image {
+        self.map_rgb(|_, channel| ((channel as f32 - 128.0) * factor + 128.0).round().clamp(0.0, 255.0) as u8)
+    }
+
+    pub fn tint(&self, color: Rgba) -> Image {
+        self.map_rgb(|c, channel| ((channel as u16 * color[c] as u16 + 127) / 255) as u8)
+    }
+
+    fn map_rgb(&self, f: impl Fn(Color, u8) -> u8) -> Image {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|&pixel| {
+                let mut out = pixel;
+                for c in Color::rgb() {
+                    out[c] = f(c, pixel[c]);
+                }
+                out
+            })
+            .collect();
+
+        Image { bytes, width: self.width, height: self.height }
+    }
+
+    pub fn to_mono_dithered(&self, threshold: u8) -> MonoImage {
+        let width = self.width as usize;
+        let mut bytes = Vec::with_capacity(self.bytes.len());
+
+        // error rows are padded by one on each side so the edges need no special casing
+        let mut current = vec![0i32; width + 2];
+        let mut next = vec![0i32; width + 2];
+
+        for row in self.bytes.chunks_exact(width.max(1)) {
+            for (x, pixel) in row.iter().enumerate() {
+                let value = pixel.luminance() as i32 + current[x + 1] / 16;
+                let out = if value >= threshold as i32 { 255 } else { 0 };
+                let error = value - out;
+
+                current[x + 2] += error * 7;
+                next[x] += error * 3;
+                next[x + 1] += error * 5;
+                next[x + 2] += error;
+
+                bytes.push(out as u8);
+            }
+
+            std::mem::swap(&mut current, &mut next);
+            next.fill(0);
+        }
+
+        MonoImage { bytes, width: self.width, height: self.height }
+    }
+
+    pub fn to_mono_threshold(&self, cutoff: u8) -> MonoImage {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|pixel| if pixel.luminance() >= cutoff { 255 } else { 0 })
+            .collect();
+
+        MonoImage { bytes, width: self.width, height: self.height }
+    }
+
+    // sizes other than 2, 4 or 8 fall back to 4
+    pub fn to_mono_bayer(&self, matrix_size: u8) -> MonoImage {
+        let bits = match matrix_size {
+            2 => 1,
+            8 => 3,
+            _ => 2
+        };
+        let cells = 1u32 << (2 * bits);
+
+        let mut bytes = Vec::with_capacity(self.bytes.len());
+        for (i, pixel) in self.bytes.iter().enumerate() {
+            let x = i as u32 % self.width;
+            let y = i as u32 / self.width;
+
+            let rank = bayer_rank(x, y, bits);
+            let white = 2 * cells * pixel.luminance() as u32 > (2 * rank + 1) * 255;
+            bytes.push(if white { 255 } else { 0 });
+        }
+
+        MonoImage { bytes, width: self.width, height: self.height }
+    }
+
+    pub fn sobel_edges(&self) -> MonoImage {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let luminance: Vec<i32> = self.bytes.iter().map(|pixel| pixel.luminance() as i32).collect();
+
+        let at = |x: i64, y: i64| luminance[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize];
+
+        let mut bytes = Vec::with_capacity(self.bytes.len());
+        for y in 0..height {
+            for x in 0..width {
+                let gx = (at(x + 1, y - 1) + 2 * at(x + 1, y) + at(x + 1, y + 1))
+                    - (at(x - 1, y - 1) + 2 * at(x - 1, y) + at(x - 1, y + 1));
+                let gy = (at(x - 1, y + 1) + 2 * at(x, y + 1) + at(x + 1, y + 1))
+                    - (at(x - 1, y - 1) + 2 * at(x, y - 1) + at(x + 1, y - 1));
+
+                let magnitude = ((gx * gx + gy * gy) as f32).sqrt();
+                bytes.push(magnitude.min(255.0) as u8);
+            }
+        }
+
+        MonoImage { bytes, width: self.width, height: self.height }
+    }
+}
+
+// position of (x, y) in the recursive Bayer ordering, the low coordinate bits pick the high digits
+fn bayer_rank(x: u32, y: u32, bits: u32) -> u32 {
+    let mut rank = 0;
+    for bit in 0..bits {
+        let (bx, by) = ((x >> bit) & 1, (y >> bit) & 1);
+        rank = (rank << 2) | ((bx ^ by) << 1) | by;
+    }
+    rank
+}
+
+#[derive(Debug)]
+pub enum ImageDimError {
+    LengthMismatch(usize, usize),
+    KernelMismatch(usize, usize)
+}
+
+impl Display for ImageDimError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use ImageDimError::*;
+
+        match self {
+            LengthMismatch(expected, found) => {
+                write!(f, "Pixel buffer length was {found}, width * height requires {expected}.")
+            }
+            KernelMismatch(expected, found) => {
+                write!(f, "Kernel length was {found}, kernel width * height requires {expected}.")
+            }
+        }
+    }
+}
+
+impl Error for ImageDimError {}
+
+// the pixels are left out, they would drown everything else
+impl std::fmt::Debug for Image {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("bytes", &self.bytes.len())
+            .finish()
+    }
+}
+
+impl ColorRect<Rgba> for Image {
+    fn get_bytes(&self) -> &[Rgba] {
+        self.bytes.as_slice()
+    }
+
+    fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    fn get_height(&self) -> u32 {
+        self.height
+    }
+}
+
+#[derive(Clone)]
+pub struct MonoImage {
+    pub bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32
+}
+
+impl MonoImage {
+
+    pub fn to_image(&self, black: Rgba, white: Rgba) -> Image {
+        let bytes = self.bytes.iter().map(|&byte| mono_color(byte, black, white)).collect();
+        Image { bytes, width: self.width, height: self.height }
+    }
+}
+
+fn mono_color(byte: u8, black: Rgba, white: Rgba) -> Rgba {
+    match byte {
+        0 => { black },
+        255 => { white },
+        b => { black.blend(white, b) }
+    }
+}
+
+impl std::fmt::Debug for MonoImage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MonoImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("bytes", &self.bytes.len())
+            .finish()
+    }
+}
+
+impl ColorRect<u8, u8> for MonoImage {
+    fn get_bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+
+    fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    fn get_width(&self) -> u32 {
+        self.width
+    }
+}
+
+// borrowed pixels that aren't owned by an Image
+pub struct SliceRect<'a> {
+    pub bytes: &'a [Rgba],
+    pub width: u32,
+    pub height: u32
+}
+
+impl ColorRect<Rgba> for SliceRect<'_> {
+    fn get_bytes(&self) -> &[Rgba] {
+        self.bytes
+    }
+
+    fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    fn get_height(&self) -> u32 {
+        self.height
+    }
+}
+
+pub struct ImageRef<'a> {
+    bytes: &'a [Rgba],
+    width: u32,
+    height: u32
+}
+
+impl ColorRect<Rgba> for ImageRef<'_> {
+    fn get_bytes(&self) -> &[Rgba] {
+        self.bytes
+    }
+
+    fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    fn get_height(&self) -> u32 {
+        self.height
+    }
+}
+
+impl ImageRef<'_> {
+
+    pub fn crop(&self, x: u32, y: u32, w: u32, h: u32) -> Option<Image> {
+        if x.checked_add(w)? > self.width || y.checked_add(h)? > self.height {
+            return None;
+        }
+
+        let mut bytes = Vec::with_capacity(w as usize * h as usize);
+        for row in y..(y + h) {
+            let start = row as usize * self.width as usize + x as usize;
+            bytes.extend_from_slice(&self.bytes[start..start + w as usize]);
+        }
+
+        Some(Image { bytes, width: w, height: h })
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        if x < self.width && y < self.height {
+            Some(self.bytes[y as usize * self.width as usize + x as usize])
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::raw_window_handle::{DisplayHandle, HandleError, WindowHandle};
+
+    // stands in for the display and window, owned pixels never ask for a handle
+    struct Dummy;
+
+    impl HasDisplayHandle for Dummy {
+        fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+            Err(HandleError::Unavailable)
+        }
+    }
+
+    impl HasWindowHandle for Dummy {
+        fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+            Err(HandleError::Unavailable)
+        }
+    }
+
+    fn size(w: u32, h: u32) -> (NonZeroU32, NonZeroU32) {
+        (NonZeroU32::new(w).unwrap(), NonZeroU32::new(h).unwrap())
+    }
+
+    fn canvas(w: u32, h: u32) -> Canvas<'static, Dummy, Dummy> {
+        let (width, height) = size(w, h);
+        Canvas::from_pixels(Pixels::Owned(vec![0; (w * h) as usize]), width, height)
+    }
+
+    #[test]
+    fn resize_to_zero_keeps_the_other_dimension() {
+        let image = Image::new_filled(4, 3, Rgba::RED);
+
+        for resized in [image.resize_nearest(5, 0), image.resize_bilinear(5, 0)] {
+            assert_eq!((resized.width, resized.height, resized.bytes.len()), (5, 0, 0));
+        }
+        for resized in [image.resize_nearest(0, 2), image.resize_bilinear(0, 2)] {
+            assert_eq!((resized.width, resized.height, resized.bytes.len()), (0, 2, 0));
+        }
+
+        let empty = Image::new_filled(0, 3, Rgba::RED);
+        let resized = empty.resize_nearest(2, 2);
+        assert_eq!((resized.width, resized.height), (2, 2));
+        assert!(resized.bytes.iter().all(|&pixel| pixel == Rgba::new(0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn resize_nearest_samples_pixel_centres() {
+        let image = Image::from_raw(2, 1, vec![Rgba::RED, Rgba::BLUE]).unwrap();
+        let wide = image.resize_nearest(4, 2);
+        assert_eq!(wide.bytes, vec![Rgba::RED, Rgba::RED, Rgba::BLUE, Rgba::BLUE, Rgba::RED, Rgba::RED, Rgba::BLUE, Rgba::BLUE]);
+        assert_eq!(wide.resize_nearest(2, 1).bytes, image.bytes);
+    }
+
+    #[test]
+    fn gaussian_blur_with_huge_sigma() {
+        let mut image = Image::new_filled(5, 3, Rgba::BLACK);
+        image.set_pixel(2, 1, Rgba::WHITE);
+
+        for sigma in [1e6, f32::INFINITY] {
+            let blurred = image.gaussian_blur(sigma);
+            assert_eq!((blurred.width, blurred.height), (5, 3));
+            assert!(blurred.bytes.iter().all(|pixel| pixel.red() < 255 && pixel.alpha() == 255));
+        }
+
+        assert_eq!(image.gaussian_blur(f32::NAN).bytes, image.bytes);
+        assert_eq!(image.gaussian_blur(0.0).bytes, image.bytes);
+    }
+
+    #[test]
+    fn brightness_and_contrast_saturate() {
+        let image = Image::from_raw(2, 1, vec![Rgba::new(10, 128, 250, 77), Rgba::new(0, 1, 255, 255)]).unwrap();
+
+        assert!(image.adjust_brightness(255).bytes.iter().all(|pixel| (pixel.red(), pixel.green(), pixel.blue()) == (255, 255, 255)));
+        assert!(image.adjust_brightness(i16::MAX).bytes.iter().all(|pixel| pixel.red() == 255 && pixel.blue() == 255));
+        assert!(image.adjust_brightness(i16::MIN).bytes.iter().all(|pixel| (pixel.red(), pixel.green(), pixel.blue()) == (0, 0, 0)));
+        assert_eq!(image.adjust_brightness(-20).get_pixel(0, 0), Some(Rgba::new(0, 108, 230, 77)));
+
+        assert!(image.adjust_contrast(0.0).bytes.iter().all(|pixel| (pixel.red(), pixel.green(), pixel.blue()) == (128, 128, 128)));
+        assert_eq!(image.adjust_contrast(1.0).bytes, image.bytes);
+        assert_eq!(image.adjust_contrast(0.0).get_pixel(0, 0).unwrap().alpha(), 77);
+    }
+
+    // hands out one byte per read, like a slow pipe or socket
+    struct Trickle(Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let end = buf.len().min(1);
+            self.0.read(&mut buf[..end])
+        }
+    }
+
+    impl Seek for Trickle {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    fn encode(image: &Image, format: ImageFormat) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgba8(image.to_image_rgba()).to_rgb8().write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn from_reader_sniffs_short_reads() {
+        let image = Image::from_raw(2, 2, vec![Rgba::RED, Rgba::GREEN, Rgba::BLUE, Rgba::WHITE]).unwrap();
+
+        let png = Image::from_reader(Trickle(Cursor::new(encode(&image, ImageFormat::Png)))).unwrap();
+        assert_eq!(png.bytes, image.bytes);
+
+        let jpeg = Image::from_reader(Trickle(Cursor::new(encode(&image, ImageFormat::Jpeg)))).unwrap();
+        assert_eq!((jpeg.width, jpeg.height), (2, 2));
+
+        assert!(Image::from_reader(Cursor::new(b"nope".to_vec())).is_err());
+        assert!(Image::from_reader(Cursor::new(b"GIF89a\0\0\0\0".to_vec())).is_err());
+    }
+
+    #[test]
+    fn resize_updates_the_reported_size() {
+        let mut canvas = canvas(4, 4);
+        canvas.fill(Rgba::RED);
+
+        let (width, height) = size(6, 2);
+        canvas.replace_pixels(Pixels::Owned(vec![0; 12]), width, height);
+        assert_eq!((canvas.width().get(), canvas.height().get()), (6, 2));
+
+        canvas.draw_rectangle(0, 0, 100, 100, Rgba::WHITE);
+        assert!(canvas.data().iter().all(|&pixel| pixel == u32::from(Rgba::WHITE)));
+    }
+
+    #[test]
+    fn resize_keeps_supersampling() {
+        let mut canvas = canvas(2, 2);
+        canvas.set_supersampling(size(2, 2).0, size(2, 2).1, 2);
+        assert_eq!((canvas.width().get(), canvas.height().get()), (4, 4));
+
+        let (width, height) = size(3, 1);
+        canvas.replace_pixels(Pixels::Owned(vec![0; 3]), width, height);
+        assert_eq!((canvas.width().get(), canvas.height().get()), (6, 2));
+        assert_eq!(canvas.data().len(), 12);
+    }
+
+
+    #[test]
+    fn draw_image_scaled_rejects_bad_scales() {
+        let image = Image::new_filled(2, 2, Rgba::RED);
+        let mut canvas = canvas(4, 4);
+
+        for scale in [0.0, -1.0, f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_eq!(canvas.draw_image_scaled(0, 0, &image, scale, 1.0), ImageCompletion::None);
+            assert_eq!(canvas.draw_image_scaled(0, 0, &image, 1.0, scale), ImageCompletion::None);
+        }
+        assert!(canvas.data().iter().all(|&pixel| pixel == 0));
+
+        // a huge but finite scale saturates the extent instead of overflowing
+        assert_eq!(canvas.draw_image_scaled(1, 1, &image, f32::MAX, f32::MAX), ImageCompletion::Partial);
+        assert_eq!(canvas.draw_image_scaled(0, 0, &image, 2.0, 2.0), ImageCompletion::Complete);
+        assert!(canvas.data().iter().all(|&pixel| pixel == u32::from(Rgba::RED)));
+    }
+
+
+    #[test]
+    fn draw_image_ref_matches_image() {
+        let bytes = (0..12u8).map(|i| Rgba::new(i * 20, 255 - i, i, 255)).collect();
+        let image = Image::from_raw(4, 3, bytes).unwrap();
+
+        let (mut direct, mut borrowed) = (canvas(6, 5), canvas(6, 5));
+        for (x, y) in [(0, 0), (3, 3), (-2, -1)] {
+            assert_eq!(direct.draw_image(x, y, &image), borrowed.draw_image(x, y, &image.get_ref()));
+        }
+        assert_eq!(direct.data(), borrowed.data());
+    }
+
+    const WHITE: u32 = 0xFFFFFFFF;
+
+    fn lit(canvas: &Canvas<'_, Dummy, Dummy>) -> usize {
+        canvas.data().iter().filter(|&&pixel| pixel != 0).count()
+    }
+
+    #[test]
+    fn lines_include_both_endpoints() {
+        let mut canvas = canvas(8, 8);
+        canvas.draw_line(0, 0, 4, 4, Rgba::WHITE);
+        for y in 0..8 {
+            for x in 0..8 {
+                assert_eq!(canvas.data()[y * 8 + x] == WHITE, x == y && x <= 4, "{x},{y}");
+            }
+        }
+
+        // lines leaving the canvas are clipped rather than wrapping onto other rows
+        let mut canvas = self::canvas(8, 8);
+        canvas.draw_line(-3, 5, 30, 5, Rgba::WHITE);
+        canvas.draw_line(2, -3, 2, 20, Rgba::WHITE);
+        assert_eq!(lit(&canvas), 15);
+    }
+
+    #[test]
+    fn lines_with_far_endpoints_are_clipped() {
+        let mut canvas = canvas(8, 8);
+        canvas.draw_line(i64::MIN / 2 - 10, 0, i64::MAX / 2 + 10, 3, Rgba::WHITE);
+        canvas.draw_line(i64::MIN, i64::MIN, i64::MAX, i64::MAX, Rgba::WHITE);
+        canvas.draw_line(0, 7, 1 << 40, 7, Rgba::WHITE);
+        canvas.draw_line(0, 6, 1 << 40, 5, Rgba::WHITE);
+        assert_eq!(canvas.data()[8 * 7..].iter().filter(|&&pixel| pixel == WHITE).count(), 8);
+
+        // a far endpoint gives the same pixels as walking the whole line would
+        let mut clipped = self::canvas(8, 8);
+        clipped.draw_line(-20, -3, 40, 9, Rgba::WHITE);
+        let mut wide = self::canvas(70, 20);
+        wide.draw_line(0, 0, 60, 12, Rgba::WHITE);
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = wide.data()[(y + 3) * 70 + x + 20];
+                assert_eq!(clipped.data()[y * 8 + x], inside, "{x},{y}");
+            }
+        }
+    }
+
+    #[test]
+    fn antialiased_line_coverage() {
+        let mut canvas = canvas(12, 6);
+        canvas.draw_line_aa(1.0, 2.0, 10.0, 3.0, Rgba::WHITE);
+        let total: u32 = canvas.data().iter().map(|&pixel| Rgba::from(pixel).red() as u32).sum();
+        assert!((9 * 255..=11 * 255).contains(&total), "{total}");
+    }
+
+    #[test]
+    fn antialiased_lines_are_clipped() {
+        let mut canvas = canvas(8, 8);
+        canvas.draw_line_aa(-3.3, -2.0, 8.0, 9.0, Rgba::WHITE);
+        canvas.draw_line_aa(f32::NEG_INFINITY, 0.0, f32::INFINITY, 3.0, Rgba::WHITE);
+        canvas.draw_line_aa(0.0, f32::NAN, 3.0, 3.0, Rgba::WHITE);
+        assert!(lit(&canvas) > 0);
+
+        // a nearly flat line across the whole f32 range still lands on its row
+        let mut canvas = self::canvas(8, 8);
+        canvas.draw_line_aa(-f32::MAX, 4.0, f32::MAX, 4.0, Rgba::WHITE);
+        for y in 0..8 {
+            assert_eq!(canvas.data()[y * 8..(y + 1) * 8].iter().all(|&pixel| pixel == WHITE), y == 4, "{y}");
+        }
+
+        // clipping keeps the coverage an unclipped line would give, up to f32 rounding
+        let mut clipped = self::canvas(8, 8);
+        clipped.draw_line_aa(-20.0, -3.5, 40.0, 9.5, Rgba::WHITE);
+        let mut wide = self::canvas(70, 20);
+        wide.draw_line_aa(0.0, -0.5, 60.0, 12.5, Rgba::WHITE);
+        for y in 0..8 {
+            for x in 0..8 {
+                let (a, b) = (Rgba::from(clipped.data()[y * 8 + x]), Rgba::from(wide.data()[(y + 3) * 70 + x + 20]));
+                assert!(a.red().abs_diff(b.red()) <= 2, "{x},{y} {a} {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn thick_lines_span_the_thickness() {
+        let mut canvas = canvas(12, 8);
+        canvas.draw_thick_line(2, 3, 8, 3, 3, Rgba::WHITE);
+        for y in 2..5 {
+            assert_eq!(canvas.get_pixel(5, y), Some(Rgba::WHITE), "{y}");
+        }
+        assert_eq!(canvas.get_pixel(5, 0), Some(Rgba::from(0)));
+        assert_eq!(canvas.get_pixel(5, 6), Some(Rgba::from(0)));
+
+        let mut canvas = self::canvas(12, 8);
+        canvas.draw_thick_line(-10, -10, -5, -5, 4, Rgba::WHITE);
+        assert_eq!(lit(&canvas), 0);
+    }
+
+    #[test]
+    fn circles_reach_their_radius() {
+        let mut canvas = canvas(25, 25);
+        canvas.draw_circle(12, 12, 10, Rgba::WHITE);
+        canvas.draw_circle(0, 0, 0, Rgba::WHITE);
+        canvas.draw_circle(30, 12, 10, Rgba::WHITE);
+        for (x, y) in [(22, 12), (2, 12), (12, 2), (12, 22), (0, 0)] {
+            assert_eq!(canvas.data()[y * 25 + x], WHITE, "{x},{y}");
+        }
+        assert_eq!(canvas.data()[12 * 25 + 12], 0);
+    }
+
+    #[test]
+    fn filled_circles_reach_their_radius() {
+        let mut canvas = canvas(25, 25);
+        canvas.fill_circle(12, 12, 10, Rgba::WHITE);
+        canvas.fill_circle(-2, 30, 5, Rgba::WHITE);
+        for x in 12..=22 {
+            assert_eq!(canvas.data()[12 * 25 + x], WHITE, "{x}");
+        }
+        assert_eq!(canvas.data()[12 * 25 + 23], 0);
+    }
+
+    #[test]
+    fn huge_filled_circles_cover_the_canvas() {
+        let mut canvas = canvas(6, 4);
+        canvas.fill_circle(3, 2, u32::MAX, Rgba::WHITE);
+        assert_eq!(lit(&canvas), 24);
+
+        // centred far away, only the topmost point reaches the canvas
+        let mut canvas = self::canvas(6, 4);
+        canvas.fill_circle(i64::MIN, i64::MAX, u32::MAX, Rgba::WHITE);
+        canvas.fill_circle(3, u32::MAX as i64 + 3, u32::MAX, Rgba::WHITE);
+        assert_eq!(lit(&canvas), 1);
+        assert_eq!(canvas.get_pixel(3, 3), Some(Rgba::WHITE));
+    }
+
+    #[test]
+    fn ellipses_are_symmetric() {
+        for (rx, ry) in [(10u32, 5u32), (3, 9), (0, 4), (5, 0), (0, 0), (1, 1)] {
+            let mut canvas = canvas(25, 21);
+            canvas.draw_ellipse(12, 10, rx, ry, Rgba::WHITE);
+            let data = canvas.data();
+            for y in 0..21 {
+                for x in 0..25 {
+                    assert_eq!(data[y * 25 + x], data[y * 25 + 24 - x], "{rx},{ry} {x},{y}");
+                    assert_eq!(data[y * 25 + x], data[(20 - y) * 25 + x], "{rx},{ry} {x},{y}");
+                }
+            }
+            assert_eq!(data[10 * 25 + 12 + rx as usize], WHITE);
+        }
+
+        let mut canvas = canvas(25, 21);
+        canvas.fill_ellipse(12, 10, 10, 5, Rgba::WHITE);
+        assert_eq!(canvas.get_pixel(12, 10), Some(Rgba::WHITE));
+        assert_eq!(canvas.get_pixel(12, 4), Some(Rgba::from(0)));
+    }
+
+    #[test]
+    fn large_ellipses_do_not_overflow() {
+        let mut canvas = canvas(8, 8);
+        canvas.draw_ellipse(0, 0, 40000, 40000, Rgba::WHITE);
+        canvas.draw_ellipse(4, 4, 1 << 20, 3, Rgba::WHITE);
+        assert_eq!(canvas.get_pixel(4, 7), Some(Rgba::WHITE));
+
+        let mut canvas = self::canvas(8, 8);
+        canvas.fill_ellipse(0, 0, 60000, 60000, Rgba::WHITE);
+        assert_eq!(lit(&canvas), 64);
+
+        // only the top of a huge ellipse centred below the canvas reaches it
+        let mut canvas = self::canvas(8, 8);
+        canvas.fill_ellipse(4, u32::MAX as i64 + 7, u32::MAX, u32::MAX, Rgba::WHITE);
+        assert_eq!(canvas.get_pixel(4, 7), Some(Rgba::WHITE));
+        assert_eq!(canvas.get_pixel(4, 6), Some(Rgba::from(0)));
+    }
+
+    #[test]
+    fn polygon_fill_handles_concave_shapes() {
+        let mut canvas = canvas(20, 12);
+        // an arrow pointing right with a notch cut into its tail
+        canvas.fill_polygon(&[(1, 1), (12, 1), (18, 6), (12, 11), (1, 11), (6, 6)], Rgba::WHITE);
+        assert_eq!(canvas.data()[6 * 20 + 3], 0);
+        assert_eq!(canvas.data()[6 * 20 + 8], WHITE);
+
+        // two points degenerate to a line
+        let (mut polygon, mut line) = (self::canvas(20, 12), self::canvas(20, 12));
+        polygon.fill_polygon(&[(-5, -5), (3, 11)], Rgba::WHITE);
+        line.draw_line(-5, -5, 3, 11, Rgba::WHITE);
+        assert_eq!(polygon.data(), line.data());
+    }
+
+    #[test]
+    fn rounded_rectangle_corners() {
+        let mut canvas = canvas(20, 14);
+        canvas.fill_rounded_rectangle(1, 1, 16, 10, 4, Rgba::WHITE);
+        canvas.fill_rounded_rectangle(18, 12, 2, 2, 9, Rgba::WHITE);
+        for (x, y) in [(1, 1), (16, 1), (1, 10), (16, 10)] {
+            assert_eq!(canvas.data()[y * 20 + x], 0, "{x},{y}");
+        }
+        assert_eq!(canvas.get_pixel(8, 5), Some(Rgba::WHITE));
+    }
+
+    #[test]
+    fn rectangle_outline_leaves_the_inside() {
+        let mut canvas = canvas(14, 10);
+        canvas.draw_rectangle_outline(1, 1, 12, 8, 2, Rgba::WHITE);
+        canvas.draw_rectangle_outline(-2, 8, 4, 4, 3, Rgba::WHITE);
+        assert_eq!(canvas.data()[5 * 14 + 6], 0);
+        assert_eq!(canvas.data()[14 + 6], WHITE);
+        assert_eq!(canvas.data()[2 * 14 + 6], WHITE);
+        assert_eq!(canvas.data()[3 * 14 + 6], 0);
+    }
+
+    #[test]
+    fn straight_bezier_matches_a_line() {
+        let mut curve = canvas(24, 14);
+        curve.draw_quadratic_bezier((2.0, 2.0), (12.0, 7.0), (22.0, 12.0), Rgba::WHITE);
+        let mut line = canvas(24, 14);
+        line.draw_line(2, 2, 22, 12, Rgba::WHITE);
+        assert_eq!(curve.data(), line.data());
+
+        let mut curve = canvas(24, 14);
+        curve.draw_cubic_bezier((1.0, 12.0), (4.0, -6.0), (18.0, 20.0), (22.0, 1.0), Rgba::WHITE);
+        assert_eq!((curve.get_pixel(1, 12), curve.get_pixel(22, 1)), (Some(Rgba::WHITE), Some(Rgba::WHITE)));
+    }
+
+
+    #[test]
+    fn polylines_draw_every_edge() {
+        let points = [(1, 1), (10, 1), (5, 8)];
+
+        let mut closed = canvas(12, 10);
+        closed.draw_polyline(&points, true, Rgba::WHITE);
+        assert_eq!(closed.get_pixel(5, 1), Some(Rgba::WHITE));
+        assert_eq!(closed.get_pixel(8, 4), Some(Rgba::WHITE));
+        assert_eq!(closed.get_pixel(4, 6), Some(Rgba::WHITE));
+
+        let mut open = canvas(12, 10);
+        open.draw_polyline(&points, false, Rgba::WHITE);
+        assert_eq!(open.get_pixel(8, 4), Some(Rgba::WHITE));
+        assert_eq!(open.get_pixel(4, 6), Some(Rgba::from(0)));
+    }
+
+
+    #[test]
+    fn dashes_alternate_along_the_line() {
+        let mut canvas = canvas(12, 10);
+        canvas.draw_dashed_line(0, 0, 9, 0, 2, 2, Rgba::WHITE);
+        assert_eq!(canvas.data()[..12].iter().filter(|&&pixel| pixel == WHITE).count(), 6);
+    }
+
+    #[test]
+    fn clipped_dashes_keep_their_phase() {
+        let mut wide = canvas(40, 4);
+        wide.draw_dashed_line(3, 1, 39, 2, 3, 2, Rgba::WHITE);
+
+        let mut clipped = canvas(20, 4);
+        clipped.set_origin(-20, 0);
+        clipped.draw_dashed_line(3, 1, 39, 2, 3, 2, Rgba::WHITE);
+
+        for y in 0..4 {
+            for x in 0..20 {
+                assert_eq!(clipped.data()[y * 20 + x], wide.data()[y * 40 + x + 20], "({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn pixels_outside_are_ignored() {
+        let mut canvas = canvas(4, 3);
+        for (x, y) in [(3, 2), (-1, 0), (4, 0), (0, 3)] {
+            canvas.set_pixel(x, y, Rgba::RED);
+        }
+        assert_eq!(canvas.get_pixel(3, 2), Some(Rgba::RED));
+        assert_eq!(canvas.get_pixel(4, 2), None);
+        assert_eq!(canvas.get_pixel(0, -1), None);
+        assert_eq!(lit(&canvas), 1);
+    }
+
+    #[test]
+    fn flood_fill_stays_inside_the_outline() {
+        let mut canvas = canvas(10, 8);
+        canvas.draw_rectangle_outline(1, 1, 6, 5, 1, Rgba::WHITE);
+        canvas.flood_fill(3, 3, Rgba::RED);
+        canvas.flood_fill(3, 3, Rgba::RED);
+        canvas.flood_fill(30, 3, Rgba::RED);
+        assert_eq!(canvas.data().iter().filter(|&&pixel| pixel == u32::from(Rgba::RED)).count(), 12);
+    }
+
+
+    #[test]
+    fn linear_gradients_hit_their_end_colours() {
+        let mut canvas = canvas(6, 6);
+        canvas.fill_gradient_linear(0, 0, 6, 6, Rgba::RED, Rgba::BLUE, false);
+        assert_eq!(Rgba::from(canvas.data()[0]), Rgba::RED);
+        assert_eq!(Rgba::from(canvas.data()[35]), Rgba::BLUE);
+    }
+
+
+    #[test]
+    fn radial_gradients_fade_to_the_rim() {
+        let mut canvas = canvas(21, 21);
+        canvas.fill_gradient_radial(10, 10, 10, Rgba::WHITE, Rgba::RED);
+        canvas.fill_gradient_radial(-100, 10, 3, Rgba::WHITE, Rgba::RED);
+        assert_eq!(canvas.get_pixel(10, 10), Some(Rgba::WHITE));
+        assert_eq!(canvas.get_pixel(20, 10), Some(Rgba::RED));
+        assert_eq!(canvas.get_pixel(20, 11), Some(Rgba::from(0)));
+    }
+
+    #[test]
+    fn huge_radial_gradients_do_not_overflow() {
+        let mut canvas = canvas(6, 4);
+        canvas.fill_gradient_radial(3, 2, u32::MAX, Rgba::WHITE, Rgba::RED);
+        assert_eq!(canvas.get_pixel(3, 2), Some(Rgba::WHITE));
+        assert_eq!(lit(&canvas), 24);
+
+        let mut canvas = self::canvas(6, 4);
+        canvas.fill_gradient_radial(i64::MIN, i64::MAX, u32::MAX, Rgba::WHITE, Rgba::RED);
+        assert_eq!(lit(&canvas), 0);
+    }
+
+
+    #[test]
+    fn blended_images_mix_with_the_canvas() {
+        let mut canvas = canvas(4, 4);
+        canvas.fill(Rgba::WHITE);
+        canvas.draw_image_blended(1, 1, &Image::new_filled(2, 2, Rgba::new(255, 0, 0, 128)));
+        assert_eq!(canvas.get_pixel(0, 0), Some(Rgba::WHITE));
+        assert_eq!(canvas.get_pixel(1, 1), Some(Rgba::new(255, 127, 127, 255)));
+    }
+
+
+    #[test]
+    fn blend_modes() {
+        let background = Rgba::new(10, 100, 200, 255);
+        assert_eq!(BlendMode::Multiply.apply(Rgba::WHITE, background), background);
+        assert_eq!(BlendMode::Multiply.apply(Rgba::BLACK, background), Rgba::BLACK);
+        assert_eq!(BlendMode::Screen.apply(Rgba::BLACK, background), background);
+        assert_eq!(BlendMode::Screen.apply(Rgba::WHITE, background), Rgba::WHITE);
+        assert_eq!(BlendMode::Add.apply(Rgba::new(250, 0, 0, 255), background), Rgba::new(255, 100, 200, 255));
+
+        let mut canvas = canvas(3, 3);
+        canvas.fill(background);
+        canvas.draw_image_with_mode(-1, -1, &Image::new_filled(2, 2, Rgba::BLACK), BlendMode::Multiply);
+        assert_eq!(canvas.get_pixel(0, 0), Some(Rgba::BLACK));
+        assert_eq!(canvas.get_pixel(1, 0), Some(background));
+    }
+
+    #[test]
+    fn from_raw_checks_the_length() {
+        assert!(matches!(Image::from_raw(2, 2, vec![Rgba::RED; 3]), Err(ImageDimError::LengthMismatch(4, 3))));
+
+        let image = Image::from_raw(2, 2, vec![Rgba::RED; 4]).unwrap();
+        assert_eq!((image.width, image.height, image.bytes.len()), (2, 2, 4));
+
+        let filled = Image::new_filled(3, 2, Rgba::BLUE);
+        assert_eq!(filled.bytes, vec![Rgba::BLUE; 6]);
+    }
+
+
+    fn gradient() -> Image {
+        let bytes = (0..16u8).map(|i| Rgba::new(i, 0, 0, 255)).collect();
+        Image::from_raw(4, 4, bytes).unwrap()
+    }
+
+    fn reds(pixels: &[Rgba]) -> Vec<u8> {
+        pixels.iter().map(|pixel| pixel.red()).collect()
+    }
+    #[test]
+    fn crop_stays_in_bounds() {
+        let image = gradient();
+        let cropped = image.crop(2, 2, 2, 2).unwrap();
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        assert_eq!(reds(&cropped.bytes), vec![10, 11, 14, 15]);
+
+        assert!(image.crop(0, 0, 4, 4).is_some());
+        assert!(image.crop(3, 0, 2, 1).is_none());
+        assert!(image.get_ref().crop(u32::MAX, 0, 2, 1).is_none());
+    }
+
+    #[test]
+    fn resize_nearest_scales_both_ways() {
+        let image = Image::from_raw(2, 2, vec![Rgba::RED, Rgba::GREEN, Rgba::BLUE, Rgba::WHITE]).unwrap();
+        let up = image.resize_nearest(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(up.bytes[y * 4 + x], image.bytes[(y / 2) * 2 + x / 2]);
+            }
+        }
+
+        assert_eq!(reds(&gradient().resize_nearest(2, 2).bytes), vec![5, 7, 13, 15]);
+    }
+
+    #[test]
+    fn resize_bilinear_interpolates() {
+        let bytes = (0..64u32).map(|i| Rgba::new((i % 16 * 17) as u8, 0, 0, (i % 16 * 10) as u8)).collect();
+        let ramp = Image::from_raw(16, 4, bytes).unwrap();
+
+        let down = ramp.resize_bilinear(5, 2);
+        assert!(down.bytes[..5].windows(2).all(|pair| pair[0].red() < pair[1].red() && pair[0].alpha() < pair[1].alpha()));
+        assert_eq!(ramp.resize_bilinear(16, 4).bytes, ramp.bytes);
+
+        let up = Image::new_filled(1, 1, Rgba::RED).resize_bilinear(3, 3);
+        assert_eq!(up.bytes, vec![Rgba::RED; 9]);
+    }
+
+    #[test]
+    fn flips_mirror_rows_and_columns() {
+        let image = gradient();
+
+        let horizontal = image.flip_horizontal();
+        assert_eq!(horizontal.flip_horizontal().bytes, image.bytes);
+        for y in 0..4 {
+            assert_eq!(horizontal.bytes[y * 4], image.bytes[y * 4 + 3]);
+            assert_eq!(horizontal.bytes[y * 4 + 3], image.bytes[y * 4]);
+        }
+
+        let vertical = image.flip_vertical();
+        assert_eq!(vertical.flip_vertical().bytes, image.bytes);
+        assert_eq!(vertical.bytes[..4], image.bytes[12..]);
+    }
+
+    #[test]
+    fn image_pixel_access() {
+        let mut image = gradient();
+        assert_eq!(image.get_pixel(1, 2).map(|pixel| pixel.red()), Some(9));
+        assert!(image.get_pixel(4, 0).is_none() && image.get_pixel(0, 4).is_none());
+
+        image.set_pixel(3, 3, Rgba::BLUE);
+        image.set_pixel(4, 3, Rgba::BLUE);
+        assert_eq!(image.get_ref().get_pixel(3, 3), Some(Rgba::BLUE));
+        assert!(image.get_ref().get_pixel(9, 9).is_none());
+    }
+
+
+    #[test]
+    fn dithering_keeps_the_average() {
+        let gray = Image::new_filled(16, 16, Rgba::new_opaque(128, 128, 128));
+        let dithered = gray.to_mono_dithered(128);
+        assert!(dithered.bytes.iter().all(|&byte| byte == 0 || byte == 255));
+        assert!((110..=146).contains(&dithered.bytes.iter().filter(|&&byte| byte == 255).count()));
+    }
+
+
+    #[test]
+    fn bayer_dithers_mid_gray_to_a_checkerboard() {
+        let gray = Image::new_filled(8, 4, Rgba::new_opaque(128, 128, 128));
+        for size in [2u8, 4, 8, 5] {
+            let bayer = gray.to_mono_bayer(size);
+            assert_eq!((bayer.width, bayer.height), (8, 4));
+            for y in 0..4usize {
+                for x in 0..8usize {
+                    assert_eq!(bayer.bytes[y * 8 + x] == 255, (x + y) % 2 == 0, "{size}");
+                }
+            }
+        }
+    }
+
+
+    #[test]
+    fn threshold_splits_at_the_level() {
+        let ramp = (0..=255u8).map(|i| Rgba::new_opaque(i, i, i)).collect();
+        let threshold = Image::from_raw(256, 1, ramp).unwrap().to_mono_threshold(100);
+        assert!(threshold.bytes[..100].iter().all(|&byte| byte == 0));
+        assert!(threshold.bytes[100..].iter().all(|&byte| byte == 255));
+    }
+
+
+    #[test]
+    fn mono_images_expand_between_two_colours() {
+        let mono = MonoImage { bytes: vec![0, 128, 255], width: 3, height: 1 };
+        let image = mono.to_image(Rgba::BLACK, Rgba::WHITE);
+        assert_eq!((image.width, image.height), (3, 1));
+        assert_eq!(image.bytes, vec![Rgba::BLACK, Rgba::new_opaque(128, 128, 128), Rgba::WHITE]);
+    }
+
+    #[test]
+    fn convolve_checks_the_kernel() {
+        let image = gradient();
+        let identity = [0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+        assert_eq!(image.convolve(&identity, 3, 3).unwrap().bytes, image.bytes);
+        assert!(matches!(image.convolve(&identity, 3, 2), Err(ImageDimError::KernelMismatch(6, 9))));
+
+        let shifted = image.convolve(&[1.0, 0.0, 0.0], 3, 1).unwrap();
+        assert_eq!(reds(&shifted.bytes[..4]), vec![0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn blurs_spread_a_point() {
+        let mut image = Image::new_filled(9, 9, Rgba::BLACK);
+        image.set_pixel(4, 4, Rgba::WHITE);
+
+        for blurred in [image.box_blur(1), image.gaussian_blur(1.0)] {
+            assert!(blurred.get_pixel(4, 4).unwrap().red() < 255);
+            assert!(blurred.get_pixel(3, 4).unwrap().red() > 0 && blurred.get_pixel(5, 5).unwrap().red() > 0);
+            assert!((200..=310).contains(&blurred.bytes.iter().map(|pixel| pixel.red() as u32).sum::<u32>()));
+            assert!(blurred.bytes.iter().all(|pixel| pixel.alpha() == 255));
+        }
+
+        assert_eq!(image.box_blur(0).bytes, image.bytes);
+    }
+
+    #[test]
+    fn box_blur_with_huge_radius() {
+        let image = Image::new_filled(5, 3, Rgba::new(90, 40, 200, 255));
+        let blurred = image.box_blur(u32::MAX);
+        assert_eq!((blurred.width, blurred.height), (5, 3));
+        assert_eq!(blurred.bytes, image.bytes);
+    }
+
+    #[test]
+    fn sobel_finds_vertical_edges() {
+        let mut image = Image::new_filled(6, 4, Rgba::BLACK);
+        for y in 0..4 {
+            for x in 3..6 {
+                image.set_pixel(x, y, Rgba::WHITE);
+            }
+        }
+
+        let edges = image.sobel_edges();
+        for row in edges.bytes.chunks_exact(6) {
+            assert_eq!(row, &[0, 0, 255, 255, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn tint_multiplies_and_keeps_alpha() {
+        let white = Image::new_filled(3, 2, Rgba::WHITE.with_alpha(90));
+        assert_eq!(white.tint(Rgba::RED).bytes, vec![Rgba::RED.with_alpha(90); 6]);
+        assert_eq!(Image::new_filled(1, 1, Rgba::BLACK).tint(Rgba::CYAN).bytes[0], Rgba::BLACK);
+    }
+
+    #[test]
+    fn trim_transparent_crops_to_content() {
+        let mut image = Image::new_filled(6, 5, Rgba::RED.with_alpha(0));
+        image.set_pixel(2, 1, Rgba::RED);
+        image.set_pixel(3, 3, Rgba::BLUE.with_alpha(1));
+
+        let trimmed = image.trim_transparent().unwrap();
+        assert_eq!((trimmed.width, trimmed.height), (2, 3));
+        assert_eq!((trimmed.bytes[0], trimmed.bytes[5]), (Rgba::RED, Rgba::BLUE.with_alpha(1)));
+
+        assert!(Image::new_filled(3, 3, Rgba::new(1, 2, 3, 0)).trim_transparent().is_none());
+    }
+
+    #[test]
+    fn split_grid_into_frames() {
+        let colors = [Rgba::RED, Rgba::GREEN, Rgba::BLUE, Rgba::WHITE];
+        let sheet = Image::from_raw(4, 2, colors.iter().flat_map(|&color| [color, color]).collect()).unwrap();
+
+        let frames = sheet.split_grid(2, 2);
+        assert_eq!(frames.len(), 4);
+        for (frame, &color) in frames.iter().zip(&colors) {
+            assert_eq!((frame.width, frame.height), (2, 1));
+            assert_eq!(frame.bytes, vec![color; 2]);
+        }
+
+        let frames = gradient().split_grid(3, 3);
+        assert_eq!(frames.len(), 9);
+        assert_eq!(frames[4].bytes[0].red(), 5);
+        assert!(gradient().split_grid(5, 1).is_empty());
+    }
+
+    #[test]
+    fn concat_matching_edges() {
+        let red = Image::new_filled(2, 2, Rgba::RED);
+        let mixed = Image::from_raw(2, 2, vec![Rgba::BLUE, Rgba::GREEN, Rgba::WHITE, Rgba::BLACK]).unwrap();
+
+        let row = Image::concat_horizontal(&[red.get_ref(), mixed.get_ref()]).unwrap();
+        assert_eq!((row.width, row.height), (4, 2));
+        assert_eq!(row.bytes, vec![Rgba::RED, Rgba::RED, Rgba::BLUE, Rgba::GREEN, Rgba::RED, Rgba::RED, Rgba::WHITE, Rgba::BLACK]);
+
+        let column = Image::concat_vertical(&[red.get_ref(), mixed.get_ref()]).unwrap();
+        assert_eq!((column.width, column.height), (2, 4));
+        assert_eq!(column.bytes[4..], mixed.bytes[..]);
+
+        let tall = Image::new_filled(1, 3, Rgba::RED);
+        assert!(Image::concat_horizontal(&[red.get_ref(), tall.get_ref()]).is_none());
+        assert!(Image::concat_vertical(&[red.get_ref(), tall.get_ref()]).is_none());
+        assert!(Image::concat_vertical(&[]).is_none());
+    }
+
+    #[test]
+    fn composite_blends_the_overlap() {
+        let mut image = Image::new_filled(4, 4, Rgba::WHITE);
+        let red = Image::new_filled(3, 3, Rgba::RED.with_alpha(128));
+        image.composite(-1, 2, &red.get_ref());
+
+        let pink = Rgba::WHITE.blend(Rgba::RED, 128);
+        for y in 0..4 {
+            for x in 0..4 {
+                let expected = if x < 2 && y >= 2 { pink } else { Rgba::WHITE };
+                assert_eq!(image.get_pixel(x, y), Some(expected), "{x},{y}");
+            }
+        }
+
+        // entirely off the image
+        image.composite(10, 10, &red.get_ref());
+        image.composite(-10, 0, &red.get_ref());
+    }
+
+
+    // ImageHandle wants a &'static str, tests leak a short path under the temp dir
+    fn temp_path(name: &str) -> &'static str {
+        let path = std::env::temp_dir().join(format!("display_lib_{}_{name}", std::process::id()));
+        Box::leak(path.to_string_lossy().into_owned().into_boxed_str())
+    }
+
+    fn load(path: &'static str) -> Result<Image, ImageError> {
+        let mut handle = ImageHandle::Handle { path };
+        handle.load()?;
+        Ok(handle.to_image().unwrap())
+    }
+
+    #[test]
+    fn save_png_round_trips_through_a_handle() {
+        let bytes = vec![Rgba::RED, Rgba::GREEN, Rgba::BLUE, Rgba::new(1, 2, 3, 4), Rgba::WHITE, Rgba::BLACK.with_alpha(0)];
+        let image = Image::from_raw(3, 2, bytes).unwrap();
+
+        let path = temp_path("round_trip.png");
+        image.save_png(path).unwrap();
+        let loaded = load(path);
+        std::fs::remove_file(path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.width, loaded.height), (3, 2));
+        assert_eq!(loaded.bytes, image.bytes);
+    }
+
+    #[test]
+    fn canvas_to_image_copies_the_pixels() {
+        let mut canvas = canvas(5, 3);
+        canvas.fill(Rgba::RED);
+        canvas.set_pixel(1, 1, Rgba::new(1, 2, 3, 4));
+
+        let image = canvas.to_image();
+        assert_eq!((image.width, image.height), (5, 3));
+        assert_eq!(image.get_pixel(1, 1), Some(Rgba::new(1, 2, 3, 4)));
+        assert_eq!(image.bytes.iter().filter(|&&pixel| pixel == Rgba::RED).count(), 14);
+    }
+
+
+    #[test]
+    fn load_converts_png_color_types() {
+        use image::{DynamicImage, ImageBuffer, Luma, LumaA, Rgb};
+
+        let decode = |name: &str, image: DynamicImage| {
+            let path = temp_path(name);
+            image.save_with_format(path, ImageFormat::Png).unwrap();
+            let loaded = load(path);
+            std::fs::remove_file(path).unwrap();
+            loaded.unwrap().bytes
+        };
+
+        let gray = ImageBuffer::from_fn(3, 1, |x, _| Luma([x as u8 * 100]));
+        assert_eq!(decode("gray.png", gray.into()), vec![Rgba::new_opaque(0, 0, 0), Rgba::new_opaque(100, 100, 100), Rgba::new_opaque(200, 200, 200)]);
+
+        let rgb = ImageBuffer::from_fn(2, 1, |x, _| Rgb([10 + x as u8, 20, 30]));
+        assert_eq!(decode("rgb.png", rgb.into()), vec![Rgba::new_opaque(10, 20, 30), Rgba::new_opaque(11, 20, 30)]);
+
+        let gray_alpha = ImageBuffer::from_fn(2, 1, |x, _| LumaA([50u8, x as u8 * 200]));
+        assert_eq!(decode("gray_alpha.png", gray_alpha.into()), vec![Rgba::new(50, 50, 50, 0), Rgba::new(50, 50, 50, 200)]);
+
+        let wide = ImageBuffer::from_fn(2, 1, |x, _| image::Rgba([65535u16, 0, 257 * 9, 32768 * x as u16]));
+        assert_eq!(decode("wide.png", DynamicImage::ImageRgba16(wide)), vec![Rgba::new(255, 0, 9, 0), Rgba::new(255, 0, 9, 128)]);
+    }
+
+    #[test]
+    fn load_sniffs_jpeg_behind_a_png_name() {
+        let image = Image::new_filled(16, 8, Rgba::new_opaque(200, 40, 90));
+        let path = temp_path("really_a_jpeg.png");
+        std::fs::write(path, encode(&image, ImageFormat::Jpeg)).unwrap();
+        let loaded = load(path);
+        std::fs::remove_file(path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.width, loaded.height), (16, 8));
+        let pixel = loaded.get_pixel(5, 5).unwrap();
+        assert!(pixel.alpha() == 255 && pixel.distance_squared(Rgba::new_opaque(200, 40, 90)) < 100, "{pixel:?}");
+    }
+
+
+    #[test]
+    fn from_png_bytes_decodes_in_memory() {
+        let image = Image::from_raw(2, 1, vec![Rgba::new_opaque(1, 2, 3), Rgba::BLUE]).unwrap();
+        let decoded = Image::from_png_bytes(&encode(&image, ImageFormat::Png)).unwrap();
+        assert_eq!((decoded.width, decoded.height), (2, 1));
+        assert_eq!(decoded.bytes, image.bytes);
+
+        assert!(Image::from_png_bytes(b"nope").is_err());
+    }
+
+
+    #[test]
+    fn from_reader_decodes_a_cursor() {
+        let image = Image::from_raw(3, 1, vec![Rgba::RED, Rgba::new_opaque(7, 8, 9), Rgba::WHITE]).unwrap();
+        let decoded = Image::from_reader(Cursor::new(encode(&image, ImageFormat::Png))).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 1));
+        assert_eq!(decoded.bytes, image.bytes);
+
+        assert!(Image::from_reader(Cursor::new(b"nope".to_vec())).is_err());
+    }
+
+    #[test]
+    fn draw_image_reports_completion() {
+        let image = Image::new_filled(3, 2, Rgba::RED);
+        let mut canvas = canvas(6, 4);
+        assert_eq!(canvas.draw_image(1, 1, &image), ImageCompletion::Complete);
+        assert_eq!(canvas.draw_image(4, 3, &image), ImageCompletion::Partial);
+        assert_eq!(canvas.draw_image(-2, -1, &image), ImageCompletion::Partial);
+        assert_eq!(canvas.draw_image(6, 0, &image), ImageCompletion::None);
+        assert_eq!(canvas.draw_image(-3, 0, &image), ImageCompletion::None);
+        assert_eq!(canvas.draw_image_blended(0, 4, &image), ImageCompletion::None);
+    }
+
+    #[test]
+    fn draw_image_clips_wide_images() {
+        let bytes = (0..30u8).map(|i| Rgba::new_opaque(i + 1, 0, 0)).collect();
+        let image = Image::from_raw(10, 3, bytes).unwrap();
+
+        for (ox, oy) in [(0isize, 0isize), (-4, 1), (2, -1), (-8, 0), (3, 2)] {
+            let mut canvas = canvas(6, 4);
+            assert_eq!(canvas.draw_image(ox, oy, &image), ImageCompletion::Partial);
+            for y in 0..4isize {
+                for x in 0..6isize {
+                    let (ix, iy) = (x - ox, y - oy);
+                    let expected = if (0..10).contains(&ix) && (0..3).contains(&iy) { u32::from(image.bytes[(iy * 10 + ix) as usize]) } else { 0 };
+                    assert_eq!(canvas.data()[(y * 6 + x) as usize], expected, "{ox},{oy} at {x},{y}");
+                }
+            }
+        }
+    }
+
+
+    #[test]
+    fn empty_rectangles_draw_nothing() {
+        for (w, h) in [(-3i64, 2i64), (-3, -2), (0, 5), (5, 0), (2, -1)] {
+            let mut canvas = canvas(8, 8);
+            canvas.draw_rectangle(4, 4, w, h, Rgba::WHITE);
+            assert_eq!(lit(&canvas), 0, "{w},{h}");
+        }
+    }
+
+
+    #[test]
+    fn rectangles_match_a_reference() {
+        for (x, y, w, h) in [(1i64, 1i64, 3i64, 2i64), (-2, -3, 5, 6), (5, 6, 10, 10), (-10, 2, 3, 3), (0, 0, 9, 7), (8, 0, 1, 1), (9, 0, 3, 3)] {
+            let mut canvas = canvas(9, 7);
+            canvas.draw_rectangle(x, y, w, h, Rgba::WHITE);
+            for gy in 0..7 {
+                for gx in 0..9 {
+                    let inside = (x..x + w).contains(&gx) && (y..y + h).contains(&gy);
+                    assert_eq!(canvas.data()[(gy * 9 + gx) as usize] == WHITE, inside, "{x},{y},{w},{h} at {gx},{gy}");
+                }
+            }
+        }
+    }
+
+    // run with --ignored --nocapture to see how long a clipped 4k fill takes
+    #[test]
+    #[ignore]
+    fn clipped_rectangle_timing() {
+        let mut canvas = canvas(3840, 2160);
+        let start = std::time::Instant::now();
+        for _ in 0..10 {
+            canvas.draw_rectangle(-5, -5, 4000, 3000, Rgba::WHITE);
+        }
+        println!("10 clipped 4k rectangles took {:?}", start.elapsed());
+        assert!(canvas.data().iter().all(|&pixel| pixel == WHITE));
+    }
+
+    #[test]
+    fn draw_image_full_rows_match_the_general_path() {
+        let bytes = (0..40u8).map(|i| Rgba::new_opaque(i + 1, 7, 0)).collect();
+        let image = Image::from_raw(8, 5, bytes).unwrap();
+
+        for y in [-6isize, -5, -2, 0, 1, 3, 6, 7] {
+            let (mut fast, mut slow) = (canvas(8, 6), canvas(8, 6));
+            fast.fill(Rgba::BLACK);
+            slow.fill(Rgba::BLACK);
+            // adding onto black takes the per pixel path but gives the same colours
+            assert_eq!(fast.draw_image(0, y, &image), slow.draw_image_with_mode(0, y, &image, BlendMode::Add), "{y}");
+            assert_eq!(fast.data(), slow.data(), "{y}");
+        }
+
+        // fewer bytes than width * height draws only the bytes there are
+        let short = Image { bytes: vec![Rgba::RED; 11], width: 8, height: 2 };
+        let mut canvas = canvas(8, 6);
+        canvas.draw_image(0, 4, &short);
+        assert_eq!(lit(&canvas), 11);
+
+        // an image with no rows is drawn completely, whichever path it takes
+        let empty = Image { bytes: Vec::new(), width: 8, height: 0 };
+        assert_eq!(canvas.draw_image(0, 0, &empty), ImageCompletion::Complete);
+        assert_eq!(canvas.draw_image_with_mode(0, 0, &empty, BlendMode::Add), ImageCompletion::Complete);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn fill_parallel_matches_fill() {
+        for (w, h) in [(3u32, 2u32), (256, 256), (1000, 333)] {
+            let (mut parallel, mut serial) = (canvas(w, h), canvas(w, h));
+            parallel.fill_parallel(Rgba::new(1, 2, 3, 4));
+            serial.fill(Rgba::new(1, 2, 3, 4));
+            assert_eq!(parallel.data(), serial.data());
+        }
+    }
+
+    #[test]
+    fn clip_rect_bounds_every_draw_call() {
+        type Draw = Box<dyn Fn(&mut Canvas<'static, Dummy, Dummy>)>;
+        let inside = |x: usize, y: usize| (2..6).contains(&x) && (1..4).contains(&y);
+
+        let mut canvas = canvas(8, 6);
+        canvas.set_clip(2, 1, 4, 3);
+        canvas.fill(Rgba::WHITE);
+        for y in 0..6 {
+            for x in 0..8 {
+                assert_eq!(canvas.data()[y * 8 + x] == WHITE, inside(x, y));
+            }
+        }
+
+        let image = Image::new_filled(8, 6, Rgba::RED);
+        let draws: Vec<(&str, Draw)> = vec![
+            ("rect", Box::new(|canvas| canvas.draw_rectangle(-1, -1, 20, 20, Rgba::WHITE))),
+            ("image", Box::new(move |canvas| assert_eq!(canvas.draw_image(0, 0, &image), ImageCompletion::Partial))),
+            ("line", Box::new(|canvas| {
+                canvas.draw_line(0, 0, 7, 5, Rgba::WHITE);
+                canvas.draw_line(3, 0, 3, 5, Rgba::WHITE);
+            })),
+            ("thick", Box::new(|canvas| canvas.draw_thick_line(0, 2, 7, 2, 4, Rgba::WHITE))),
+            ("aa", Box::new(|canvas| canvas.draw_line_aa(0.0, 0.3, 7.0, 5.2, Rgba::WHITE))),
+            ("circle", Box::new(|canvas| {
+                canvas.fill_circle(4, 3, 5, Rgba::WHITE);
+                canvas.draw_circle(4, 3, 3, Rgba::WHITE);
+            })),
+            ("gradient", Box::new(|canvas| {
+                canvas.fill_gradient_linear(0, 0, 8, 6, Rgba::WHITE, Rgba::RED, false);
+                canvas.fill_gradient_radial(4, 3, 6, Rgba::WHITE, Rgba::RED);
+            })),
+            ("polygon", Box::new(|canvas| canvas.fill_polygon(&[(0, 0), (8, 0), (4, 7)], Rgba::WHITE))),
+            ("flood", Box::new(|canvas| canvas.flood_fill(3, 2, Rgba::WHITE))),
+            ("mono", Box::new(|canvas| {
+                canvas.draw_monochrome_image::<_, u32>(0, 0, &MonoImage { bytes: vec![255; 48], width: 8, height: 6 }, Rgba::BLACK, Rgba::WHITE);
+            })),
+            ("pixel", Box::new(|canvas| {
+                canvas.set_pixel(0, 0, Rgba::WHITE);
+                canvas.set_pixel(7, 5, Rgba::WHITE);
+            }))
+        ];
+
+        for (name, draw) in draws {
+            let mut canvas = self::canvas(8, 6);
+            canvas.set_clip(2, 1, 4, 3);
+            draw(&mut canvas);
+            canvas.clear_clip();
+            canvas.set_pixel(-1, -1, Rgba::WHITE);
+
+            for y in 0..6 {
+                for x in 0..8 {
+                    if !inside(x, y) {
+                        assert_eq!(canvas.data()[y * 8 + x], 0, "{name} {x},{y}");
+                    }
+                }
+            }
+            assert_eq!(lit(&canvas) == 0, name == "pixel", "{name}");
+        }
+
+        // a clip with a negative size hides everything
+        let mut canvas = self::canvas(8, 6);
+        canvas.set_clip(0, 0, -3, 2);
+        canvas.fill(Rgba::WHITE);
+        canvas.draw_image(0, 0, &Image::new_filled(8, 6, Rgba::RED));
+        assert_eq!(lit(&canvas), 0);
+    }
+
+    #[test]
+    fn origin_shifts_every_draw_call() {
+        let mut canvas = canvas(16, 16);
+        canvas.set_origin(10, 10);
+        canvas.draw_rectangle(0, 0, 3, 2, Rgba::WHITE);
+        assert_eq!(canvas.get_pixel(0, 0), Some(Rgba::WHITE));
+        assert_eq!(canvas.get_pixel(-10, -10), Some(Rgba::from(0)));
+        for y in 0..16 {
+            for x in 0..16 {
+                assert_eq!(canvas.data()[y * 16 + x] == WHITE, (10..13).contains(&x) && (10..12).contains(&y));
+            }
+        }
+
+        let image = Image::new_filled(8, 6, Rgba::RED);
+        let wide = Image::new_filled(16, 3, Rgba::BLUE);
+        let draw = |(ox, oy): (i64, i64), canvas: &mut Canvas<'static, Dummy, Dummy>| {
+            canvas.draw_rectangle(1 - ox, 2 - oy, 20, 3, Rgba::WHITE);
+            canvas.draw_image(3 - ox as isize, 4 - oy as isize, &image);
+            canvas.draw_image(-ox as isize, 12 - oy as isize, &wide);
+            canvas.draw_line(-ox, -oy, 15 - ox, 9 - oy, Rgba::GREEN);
+            canvas.draw_line(5 - ox, -oy, 5 - ox, 15 - oy, Rgba::GREEN);
+            canvas.fill_circle(8 - ox, 8 - oy, 4, Rgba::CYAN);
+            canvas.draw_thick_line(-ox, 14 - oy, 15 - ox, 14 - oy, 3, Rgba::YELLOW);
+            canvas.draw_line_aa(0.5 - ox as f32, 3.2 - oy as f32, 14.0 - ox as f32, 11.0 - oy as f32, Rgba::WHITE);
+            canvas.fill_gradient_radial(12 - ox, 3 - oy, 3, Rgba::WHITE, Rgba::RED);
+            canvas.fill_gradient_linear(-ox, -oy, 4, 4, Rgba::WHITE, Rgba::RED, true);
+            canvas.flood_fill(15 - ox as isize, -oy as isize, Rgba::MAGENTA);
+        };
+
+        let mut expected = self::canvas(16, 16);
+        draw((0, 0), &mut expected);
+        for origin in [(3i64, -2i64), (-5, 4), (0, 7)] {
+            let mut shifted = self::canvas(16, 16);
+            shifted.set_origin(origin.0 as isize, origin.1 as isize);
+            draw(origin, &mut shifted);
+            assert_eq!(shifted.data(), expected.data(), "{origin:?}");
+        }
+
+        // the clip stays in screen space
+        let mut canvas = self::canvas(8, 8);
+        canvas.set_origin(4, 4);
+        canvas.set_clip(0, 0, 6, 6);
+        canvas.fill(Rgba::WHITE);
+        for y in 0..8 {
+            for x in 0..8 {
+                assert_eq!(canvas.data()[y * 8 + x] == WHITE, x < 6 && y < 6);
+            }
+        }
+    }
+
+    #[test]
+    fn draw_image_region_clamps_the_source() {
+        let image = Image::from_raw(2, 2, vec![Rgba::RED, Rgba::GREEN, Rgba::BLUE, Rgba::WHITE]).unwrap();
+        let opaque = |canvas: &Canvas<'_, Dummy, Dummy>| canvas.to_image().bytes.iter().filter(|pixel| pixel.alpha() != 0).count();
+
+        let mut canvas = canvas(4, 4);
+        assert_eq!(canvas.draw_image_region(1, 1, &image, 1, 0, 1, 2), ImageCompletion::Complete);
+        assert_eq!((canvas.get_pixel(1, 1), canvas.get_pixel(1, 2)), (Some(Rgba::GREEN), Some(Rgba::WHITE)));
+        assert_eq!(opaque(&canvas), 2);
+
+        let mut canvas = self::canvas(4, 4);
+        canvas.draw_image_region(0, 0, &image, 1, 1, 50, 50);
+        assert_eq!(canvas.get_pixel(0, 0), Some(Rgba::WHITE));
+        assert_eq!(opaque(&canvas), 1);
+
+        let mut canvas = self::canvas(4, 4);
+        assert_eq!(canvas.draw_image_region(0, 0, &image, 5, 5, 1, 1), ImageCompletion::Complete);
+        assert_eq!(canvas.draw_image_region(3, 3, &gradient(), 0, 0, 4, 4), ImageCompletion::Partial);
+        assert_eq!(canvas.get_pixel(3, 3).unwrap().red(), 0);
+    }
+
+    #[test]
+    fn draw_image_scaled_repeats_pixels() {
+        let image = Image::from_raw(2, 2, vec![Rgba::RED, Rgba::GREEN, Rgba::BLUE, Rgba::WHITE]).unwrap();
+
+        let mut canvas = canvas(6, 6);
+        assert_eq!(canvas.draw_image_scaled(1, 1, &image, 2.0, 2.0), ImageCompletion::Complete);
+        for y in 0..6 {
+            for x in 0..6 {
+                let pixel = canvas.get_pixel(x, y).unwrap();
+                if (1..5).contains(&x) && (1..5).contains(&y) {
+                    assert_eq!(Some(pixel), image.get_pixel((x as u32 - 1) / 2, (y as u32 - 1) / 2), "{x},{y}");
+                } else {
+                    assert_eq!(pixel.alpha(), 0, "{x},{y}");
+                }
+            }
+        }
+
+        let mut canvas = self::canvas(6, 6);
+        assert_eq!(canvas.draw_image_scaled(4, -3, &image, 3.0, 3.0), ImageCompletion::Partial);
+        assert_eq!(canvas.draw_image_scaled(9, 0, &image, 3.0, 3.0), ImageCompletion::None);
+        assert_eq!(canvas.draw_image_scaled(0, 0, &gradient(), 0.5, 1.5), ImageCompletion::Complete);
+    }
+
+    #[test]
+    fn draw_image_tiled_wraps_the_source() {
+        let image = Image::from_raw(2, 2, vec![Rgba::RED, Rgba::GREEN, Rgba::BLUE, Rgba::WHITE]).unwrap();
+
+        let mut canvas = canvas(7, 7);
+        canvas.draw_image_tiled(1, 1, 5, 5, &image);
+        for y in 0..7 {
+            for x in 0..7 {
+                let pixel = canvas.get_pixel(x, y).unwrap();
+                if (1..6).contains(&x) && (1..6).contains(&y) {
+                    assert_eq!(Some(pixel), image.get_pixel((x as u32 - 1) % 2, (y as u32 - 1) % 2), "{x},{y}");
+                } else {
+                    assert_eq!(pixel.alpha(), 0, "{x},{y}");
+                }
+            }
+        }
+
+        let mut canvas = self::canvas(4, 4);
+        canvas.draw_image_tiled(-3, -1, 10, 10, &image);
+        assert_eq!(canvas.get_pixel(0, 0), Some(Rgba::WHITE));
+
+        let mut canvas = self::canvas(4, 4);
+        canvas.draw_image_tiled(0, 0, 0, 10, &image);
+        assert_eq!(lit(&canvas), 0);
+    }
+
+    #[test]
+    fn copy_region_handles_overlap() {
+        let source = gradient();
+        for (dx, dy) in [(1isize, 0isize), (2, 2)] {
+            let mut canvas = canvas(6, 6);
+            canvas.draw_image(1, 1, &source);
+            let before = canvas.to_image();
+            canvas.copy_region(1, 1, 4, 4, dx as i64, dy as i64);
+
+            for y in 0..4 {
+                for x in 0..4 {
+                    assert_eq!(canvas.get_pixel(x + dx, y + dy), source.get_pixel(x as u32, y as u32), "{dx},{dy} {x},{y}");
+                }
+            }
+            assert_eq!(canvas.get_pixel(0, 0), before.get_pixel(0, 0));
+        }
+
+        let mut canvas = canvas(6, 6);
+        canvas.draw_image(1, 1, &source);
+        canvas.copy_region(0, 0, 6, 6, 4, -4);
+        canvas.copy_region(0, 0, 6, 6, 10, 0);
+        assert_eq!(canvas.get_pixel(5, 0), source.get_pixel(0, 3));
+    }
+
+    #[test]
+    fn copy_region_scrolls_up_a_row() {
+        let source = gradient();
+        let mut canvas = canvas(4, 4);
+        canvas.draw_image(0, 0, &source);
+        canvas.copy_region(0, 1, 4, 3, 0, 0);
+
+        for y in 0..3 {
+            for x in 0..4 {
+                assert_eq!(canvas.get_pixel(x, y), source.get_pixel(x as u32, y as u32 + 1), "{x},{y}");
+            }
+        }
+        // the vacated bottom row keeps what was there
+        for x in 0..4 {
+            assert_eq!(canvas.get_pixel(x, 3), source.get_pixel(x as u32, 3), "{x}");
+        }
+    }
+
+    #[test]
+    fn rows_and_pixels_mut_cover_the_canvas() {
+        let mut canvas = canvas(5, 3);
+        for (y, row) in canvas.rows_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = (y * 10 + x) as u32;
+            }
+        }
+        assert_eq!(canvas.rows_mut().count(), 3);
+
+        for (x, y, pixel) in canvas.pixels_mut() {
+            assert_eq!(*pixel, y * 10 + x);
+            *pixel = 7;
+        }
+        assert!(canvas.data().iter().all(|&pixel| pixel == 7));
+    }
+
+
+    #[test]
+    fn clear_rect_fills_only_the_rect() {
+        let mut canvas = canvas(6, 5);
+        canvas.fill(Rgba::RED);
+        canvas.clear_rect(1, 2, 3, 2, Rgba::WHITE);
+        for y in 0..5 {
+            for x in 0..6 {
+                assert_eq!(canvas.data()[y * 6 + x] == WHITE, (1..4).contains(&x) && (2..4).contains(&y));
+            }
+        }
+    }
+
+
+    #[test]
+    fn checkerboard_cells_follow_the_origin() {
+        let mut canvas = canvas(7, 5);
+        canvas.set_origin(-1, -1);
+        canvas.draw_checkerboard(2, Rgba::WHITE, Rgba::RED);
+        let image = canvas.to_image();
+        for y in 0..5u32 {
+            for x in 0..7u32 {
+                let even = (x.div_ceil(2) + y.div_ceil(2)) % 2 == 0;
+                assert_eq!(image.get_pixel(x, y), Some(if even { Rgba::WHITE } else { Rgba::RED }), "{x},{y}");
+            }
+        }
+
+        let mut canvas = self::canvas(3, 3);
+        canvas.draw_checkerboard(1, Rgba::WHITE, Rgba::RED);
+        assert_eq!((canvas.get_pixel(0, 0), canvas.get_pixel(1, 0)), (Some(Rgba::WHITE), Some(Rgba::RED)));
+        assert_eq!((canvas.get_pixel(0, 1), canvas.get_pixel(1, 1)), (Some(Rgba::RED), Some(Rgba::WHITE)));
+
+        let mut canvas = self::canvas(3, 3);
+        canvas.draw_checkerboard(0, Rgba::WHITE, Rgba::RED);
+        assert_eq!(lit(&canvas), 0);
+    }
+
+
+    #[test]
+    fn blended_monochrome_images_mix_by_colour_alpha() {
+        let mut canvas = canvas(3, 1);
+        canvas.fill(Rgba::RED);
+        let mask = MonoImage { bytes: vec![0, 255, 128], width: 3, height: 1 };
+        let half_white = Rgba::new(255, 255, 255, 128);
+        assert_eq!(canvas.draw_monochrome_image_blended(0, 0, &mask, Rgba::new(0, 0, 0, 0), half_white), ImageCompletion::Complete);
+        assert_eq!(canvas.get_pixel(0, 0), Some(Rgba::RED));
+        assert_eq!(canvas.get_pixel(1, 0), Some(half_white.over(Rgba::RED)));
+    }
+
+
+    #[test]
+    fn monochrome_completion_on_every_edge() {
+        let mono = MonoImage { bytes: vec![255; 6], width: 3, height: 2 };
+        let mut canvas = canvas(4, 4);
+        let mut draw = |x, y| canvas.draw_monochrome_image::<_, u32>(x, y, &mono, Rgba::BLACK, Rgba::WHITE);
+        for (x, y) in [(0, -10), (-10, 0), (5, 0)] {
+            assert_eq!(draw(x, y), ImageCompletion::None, "{x},{y}");
+        }
+        for (x, y) in [(-1, 0), (0, -1), (2, 2)] {
+            assert_eq!(draw(x, y), ImageCompletion::Partial, "{x},{y}");
+        }
+        assert_eq!(draw(1, 1), ImageCompletion::Complete);
+        assert_eq!(&canvas.data()[0..4], &[WHITE, WHITE, WHITE, 0]);
+    }
+
+
+    #[test]
+    fn image_clone_and_debug() {
+        let image = gradient();
+        assert_eq!(image.clone().bytes, image.bytes);
+        assert_eq!(format!("{image:?}"), "Image { width: 4, height: 4, bytes: 16 }");
+
+        let mono = MonoImage { bytes: vec![1, 2], width: 2, height: 1 };
+        assert_eq!(mono.clone().bytes, mono.bytes);
+        assert_eq!(format!("{mono:?}"), "MonoImage { width: 2, height: 1, bytes: 2 }");
+    }
+
+
+    #[test]
+    fn image_rows_step_by_width() {
+        let mut image = Image::new_filled(3, 5, Rgba::RED);
+        assert_eq!(image.rows().count(), 5);
+        assert!(image.rows().all(|row| row.len() == 3));
+        for (y, row) in image.rows_mut().enumerate() {
+            row[0] = Rgba::new(y as u8, 0, 0, 0);
+        }
+        assert_eq!(image.get_pixel(0, 4), Some(Rgba::new(4, 0, 0, 0)));
+        assert_eq!(Image::new_filled(0, 3, Rgba::RED).rows().count(), 0);
+    }
+
+    #[test]
+    fn slice_rect_draws_like_an_image() {
+        let source = gradient();
+        let (mut image, mut slice) = (canvas(6, 6), canvas(6, 6));
+        assert_eq!(image.draw_image(1, 1, &source), ImageCompletion::Complete);
+        assert_eq!(slice.draw_image(1, 1, &SliceRect { bytes: &source.bytes, width: 4, height: 4 }), ImageCompletion::Complete);
+        assert_eq!(image.data(), slice.data());
+        assert_eq!(lit(&image), 16);
+    }
+
+
+    fn test_font() -> BitmapFont {
+        let mut glyphs = std::collections::HashMap::new();
+        glyphs.insert('A', MonoImage { bytes: vec![255, 0, 255, 255], width: 2, height: 2 });
+        glyphs.insert('B', MonoImage { bytes: vec![255, 255, 255, 0, 0, 255], width: 3, height: 2 });
+        BitmapFont { glyphs, line_height: 3 }
+    }
+
+    #[test]
+    fn text_skips_unknown_glyphs() {
+        let font = test_font();
+        let mut canvas = canvas(8, 3);
+        canvas.fill(Rgba::RED);
+        canvas.draw_text(1, 0, "A?B", &font, Rgba::WHITE);
+
+        let red = u32::from(Rgba::RED);
+        assert_eq!(&canvas.data()[0..8], &[red, WHITE, red, WHITE, WHITE, WHITE, red, red]);
+        assert_eq!(&canvas.data()[8..16], &[red, WHITE, WHITE, red, red, WHITE, red, red]);
+        assert!(canvas.data()[16..].iter().all(|&pixel| pixel == red));
+    }
+
+
+    #[test]
+    fn text_aligns_each_line() {
+        let font = test_font();
+        for (align, first, second) in [(TextAlign::Left, 1, 1), (TextAlign::Center, 8, 10), (TextAlign::Right, 16, 19)] {
+            let mut canvas = canvas(22, 7);
+            canvas.draw_text_aligned(1, 1, 20, "AB\nA", &font, align, Rgba::WHITE);
+            let row = |y: usize| canvas.data()[y * 22..(y + 1) * 22].iter().position(|&pixel| pixel == WHITE);
+            assert_eq!((row(1), row(4)), (Some(first), Some(second)), "{align:?}");
+        }
+
+        let mut canvas = canvas(10, 7);
+        canvas.draw_text(0, 0, "A\nB", &font, Rgba::WHITE);
+        assert_eq!((canvas.data()[30], canvas.data()[32]), (WHITE, WHITE));
+    }
+
+
+    #[test]
+    fn draw_char_returns_the_advance() {
+        let font = test_font();
+        let mut canvas = canvas(4, 2);
+        assert_eq!(canvas.draw_char(0, 0, 'B', &font, Rgba::WHITE), 3);
+        assert_eq!(canvas.draw_char(0, 0, 'z', &font, Rgba::WHITE), 0);
+        assert_eq!(&canvas.data()[0..4], &[WHITE, WHITE, WHITE, 0]);
+    }
+
+
+    #[test]
+    fn rgba_image_round_trips() {
+        let image = Image::from_raw(2, 1, vec![Rgba::new(1, 2, 3, 4), Rgba::new(250, 0, 9, 255)]).unwrap();
+        let converted = image.to_image_rgba();
+        assert_eq!(converted.get_pixel(0, 0).0, [1, 2, 3, 4]);
+        assert_eq!(converted.get_pixel(1, 0).0, [250, 0, 9, 255]);
+
+        let back = Image::from_image_rgba(&converted);
+        assert_eq!((back.width, back.height), (2, 1));
+        assert_eq!(back.bytes, image.bytes);
+    }
+
+
+    #[test]
+    fn from_dynamic_converts_to_rgba() {
+        let rgb = image::RgbImage::from_fn(3, 2, |x, y| image::Rgb([x as u8 * 10, y as u8, 7]));
+        let dynamic = Image::from_dynamic(image::DynamicImage::ImageRgb8(rgb));
+        assert_eq!((dynamic.width, dynamic.height), (3, 2));
+        assert_eq!(dynamic.get_pixel(2, 1), Some(Rgba::new(20, 1, 7, 255)));
+    }
+
+
+    #[test]
+    fn supersampling_averages_blocks() {
+        let (width, height) = size(4, 4);
+        let mut canvas = canvas(4, 4);
+        canvas.set_supersampling(width, height, 2);
+        assert_eq!((canvas.width().get(), canvas.height().get()), (8, 8));
+
+        canvas.fill(Rgba::BLACK);
+        canvas.draw_line(0, 0, 7, 7, Rgba::RED);
+        canvas.present_downsampled();
+        for y in 0..4 {
+            for x in 0..4 {
+                let expected = if x == y { Rgba::new(128, 0, 0, 255) } else { Rgba::BLACK };
+                assert_eq!(Rgba::from(canvas.buffer[y * 4 + x]), expected, "{x},{y}");
+            }
+        }
+
+        // a factor of one draws straight into the buffer
+        let mut canvas = self::canvas(2, 2);
+        canvas.set_supersampling(size(2, 2).0, size(2, 2).1, 1);
+        assert_eq!(canvas.width().get(), 2);
+        assert!(canvas.supersample.is_none());
+    }
+
+    #[test]
+    fn handing_back_the_buffer_downsamples() {
+        let (width, height) = size(2, 1);
+        let mut canvas = canvas(2, 1);
+        canvas.set_supersampling(width, height, 2);
+        canvas.fill(Rgba::WHITE);
+
+        // destroy and present both hand the buffer over through into_pixels
+        assert_eq!(&*canvas.into_pixels(), &[WHITE, WHITE]);
+    }
+
+    #[test]
+    fn draw_image_subpixel_spreads_across_pixels() {
+        let white = Image::new_filled(1, 1, Rgba::WHITE);
+
+        let mut canvas = canvas(4, 1);
+        canvas.fill(Rgba::BLACK);
+        assert_eq!(canvas.draw_image_subpixel(1.0, 0.0, &white), ImageCompletion::Complete);
+        let black = u32::from(Rgba::BLACK);
+        assert_eq!(canvas.data(), &[black, WHITE, black, black]);
+
+        let mut canvas = self::canvas(4, 1);
+        canvas.fill(Rgba::BLACK);
+        assert_eq!(canvas.draw_image_subpixel(1.5, 0.0, &white), ImageCompletion::Complete);
+        let (left, right) = (canvas.get_pixel(1, 0).unwrap(), canvas.get_pixel(2, 0).unwrap());
+        assert!((left.red() as i32 - 128).abs() <= 1 && left.red() == right.red() && left.alpha() == 255);
+        assert_eq!((canvas.get_pixel(0, 0), canvas.get_pixel(3, 0)), (Some(Rgba::BLACK), Some(Rgba::BLACK)));
+
+        let mut canvas = self::canvas(4, 4);
+        assert_eq!(canvas.draw_image_subpixel(-0.5, 1.0, &gradient()), ImageCompletion::Partial);
+        assert_eq!(canvas.draw_image_subpixel(10.0, 1.0, &gradient()), ImageCompletion::None);
+        assert_eq!(canvas.draw_image_subpixel(f32::NAN, 1.0, &gradient()), ImageCompletion::None);
+    }
+
+    #[test]
+    fn rotation_by_right_angles_is_exact() {
+        let image = Image::from_raw(3, 3, (1..=9).map(|i| Rgba::new(i, 0, 0, 255)).collect()).unwrap();
+        assert_eq!(image.rotate_90().get_pixel(2, 0), image.get_pixel(0, 0));
+
+        let wide = Image::from_raw(3, 1, vec![Rgba::RED, Rgba::GREEN, Rgba::BLUE]).unwrap().rotate_90();
+        assert_eq!((wide.width, wide.height), (1, 3));
+        assert_eq!(wide.get_pixel(0, 0), Some(Rgba::RED));
+
+        let quarter = std::f32::consts::FRAC_PI_2;
+        for (angle, expected) in [(quarter, image.rotate_90()), (0.0, image.clone()), (2.0 * quarter, image.rotate_90().rotate_90())] {
+            let mut canvas = canvas(5, 5);
+            canvas.draw_image_rotated(2, 2, &image, angle);
+            for y in 0..5 {
+                for x in 0..5 {
+                    let pixel = canvas.get_pixel(x, y).unwrap();
+                    if (1..4).contains(&x) && (1..4).contains(&y) {
+                        assert_eq!(Some(pixel), expected.get_pixel(x as u32 - 1, y as u32 - 1), "{angle} {x},{y}");
+                    } else {
+                        assert_eq!(pixel.alpha(), 0, "{angle} {x},{y}");
+                    }
+                }
+            }
+        }
+
+        let mut canvas = canvas(4, 4);
+        canvas.draw_image_rotated(2, 2, &Image::new_filled(2, 2, Rgba::RED), quarter);
+        canvas.draw_image_rotated(-20, 0, &image, 0.7);
+        assert_eq!(lit(&canvas), 4);
+    }
+
+    #[test]
+    fn draw_masked_blends_by_coverage() {
+        let mask = MonoImage { bytes: vec![0, 255, 0, 255, 255, 255, 0, 255, 0], width: 3, height: 3 };
+        let mut canvas = canvas(5, 5);
+        canvas.fill(Rgba::RED);
+        canvas.draw_masked(1, 1, &mask, Rgba::WHITE);
+        canvas.draw_masked(-1, -1, &mask, Rgba::WHITE);
+
+        let red = u32::from(Rgba::RED);
+        for y in 0..5 {
+            for x in 0..5 {
+                let cross = (x == 2 && (1..4).contains(&y)) || (y == 2 && (1..4).contains(&x)) || [(0, 0), (1, 0), (0, 1)].contains(&(x, y));
+                assert_eq!(canvas.data()[y * 5 + x], if cross { WHITE } else { red }, "{x},{y}");
+            }
+        }
+
+        let mut canvas = self::canvas(1, 1);
+        canvas.fill(Rgba::BLACK);
+        canvas.draw_masked(0, 0, &MonoImage { bytes: vec![128], width: 1, height: 1 }, Rgba::WHITE);
+        assert_eq!(Rgba::from(canvas.data()[0]).red(), 128);
+    }
 }
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use crate::canvas::MonoImage;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right
+}
+
+// glyphs are coverage masks drawn top-left aligned on each line, the glyph width is its advance
+pub struct BitmapFont {
+    pub glyphs: HashMap<char, MonoImage>,
+    pub line_height: u32
+}
+
+impl BitmapFont {
+
+    // the widest line by the number of lines, unknown characters take no space
+    pub fn measure(&self, text: &str) -> (u32, u32) {
+        if text.is_empty() {
+            return (0, 0);
+        }
+
+        let mut width = 0;
+        let mut lines = 0;
+
+        for line in text.split('\n') {
+            let line_width = line.chars()
+                .filter_map(|ch| self.glyphs.get(&ch))
+                .map(|glyph| glyph.width)
+                .sum();
+
+            width = width.max(line_width);
+            lines += 1;
+        }
+
+        (width, lines * self.line_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_lines() {
+        let mut glyphs = HashMap::new();
+        glyphs.insert('A', MonoImage { bytes: vec![255; 4], width: 2, height: 2 });
+        glyphs.insert('B', MonoImage { bytes: vec![255; 6], width: 3, height: 2 });
+        let font = BitmapFont { glyphs, line_height: 3 };
+
+        assert_eq!(font.measure("AB"), (5, 3));
+        assert_eq!(font.measure("A\nBB?B"), (9, 6));
+        assert_eq!(font.measure("\n"), (0, 6));
+        assert_eq!(font.measure(""), (0, 0));
+    }
+}
@@ -1,6 +1,8 @@
 
 pub mod canvas;
+pub mod font;
 pub mod rgba;
 
 pub use canvas::*;
+pub use font::*;
 pub use rgba::*;
@@ -2,7 +2,7 @@ use std::error::Error;
 use std::fmt::{Formatter, Display};
 
 use std::ops::{Index, IndexMut};
-use std::str::Chars;
+use std::str::{Chars, FromStr};
 
 use std::num::TryFromIntError;
 
@@ -14,10 +14,21 @@ pub enum Color {
     Alpha = 3
 }
 
-#[derive(Clone, Copy)]
-pub union Rgba {
-    value: [u8; 4],
-    number: u32
+impl Color {
+
+    pub const ALL: [Color; 4] = [Color::Red, Color::Green, Color::Blue, Color::Alpha];
+
+    pub const fn rgb() -> [Color; 3] {
+        [Color::Red, Color::Green, Color::Blue]
+    }
+}
+
+// stored as [blue, green, red, alpha] so that the little-endian u32 is softbuffer's 0xAARRGGBB,
+// aligned like a u32 so pixel slices can be reinterpreted in place
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(C, align(4))]
+pub struct Rgba {
+    value: [u8; 4]
 }
 
 impl Rgba {
@@ -50,54 +61,302 @@ impl Rgba {
         }
     }
 
-    pub fn blend(mut self, rhs: Self, proportion: u8) -> Self {
-        use Color::*;
+    // takes 0xRRGGBBAA, unlike From<u32> which takes softbuffer's 0xAARRGGBB
+    pub const fn from_hex_u32(rgba: u32) -> Self {
+        let [red, green, blue, alpha] = rgba.to_be_bytes();
+        Self::new(red, green, blue, alpha)
+    }
 
-        for c in [Red, Blue, Green] {
+    pub fn from_name(name: &str) -> Option<Rgba> {
+        let name = name.to_ascii_lowercase();
+
+        NAMED_COLORS
+            .binary_search_by(|(entry, _)| (*entry).cmp(name.as_str()))
+            .ok()
+            .map(|index| NAMED_COLORS[index].1)
+    }
+
+    pub fn red(&self) -> u8 {
+        self[Color::Red]
+    }
+
+    pub fn green(&self) -> u8 {
+        self[Color::Green]
+    }
+
+    pub fn blue(&self) -> u8 {
+        self[Color::Blue]
+    }
+
+    pub fn alpha(&self) -> u8 {
+        self[Color::Alpha]
+    }
+
+    pub fn with_alpha(&self, a: u8) -> Rgba {
+        let mut out = *self;
+        out[Color::Alpha] = a;
+        out
+    }
+
+    pub fn blend(mut self, rhs: Self, proportion: u8) -> Self {
+        for c in Color::ALL {
             self[c] = blend_color(self[c], rhs[c], proportion);
         }
 
         self
     }
+
+    pub fn lerp(&self, other: Rgba, t: f32) -> Rgba {
+        let t = t.clamp(0.0, 1.0);
+
+        let mut out = *self;
+        for i in 0..4 {
+            let (a, b) = (self[i] as f32, other[i] as f32);
+            out[i] = (a + (b - a) * t).round() as u8;
+        }
+        out
+    }
+
+    pub fn to_hex_string(&self) -> String {
+        use Color::*;
+        format!("{:02X}{:02X}{:02X}{:02X}", self[Red], self[Green], self[Blue], self[Alpha])
+    }
+
+    pub fn to_hex_string_rgb(&self) -> String {
+        use Color::*;
+
+        if self[Alpha] == 255 {
+            format!("{:02X}{:02X}{:02X}", self[Red], self[Green], self[Blue])
+        } else {
+            self.to_hex_string()
+        }
+    }
+
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+
+        let h = h.rem_euclid(360.0) / 60.0;
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let m = v - c;
+
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x)
+        };
+
+        let to_u8 = |n: f32| ((n + m) * 255.0).round() as u8;
+        Self::new_opaque(to_u8(r), to_u8(g), to_u8(b))
+    }
+
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        use Color::*;
+
+        let (r, g, b) = (self[Red] as f32 / 255.0, self[Green] as f32 / 255.0, self[Blue] as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+
+        (h, s, max)
+    }
+
+    // [red, green, blue, alpha], alpha is already linear so it is only normalised
+    pub fn to_linear(&self) -> [f32; 4] {
+        use Color::*;
+
+        [
+            srgb_to_linear(self[Red]),
+            srgb_to_linear(self[Green]),
+            srgb_to_linear(self[Blue]),
+            self[Alpha] as f32 / 255.0
+        ]
+    }
+
+    pub fn from_linear(channels: [f32; 4]) -> Rgba {
+        let [r, g, b, a] = channels;
+        Rgba::new(linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), (a.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    // raises each channel to 1 / gamma, so gammas above 1 brighten
+    pub fn gamma_correct(&self, gamma: f32) -> Rgba {
+        let exponent = 1.0 / gamma;
+        let mut out = *self;
+        for c in Color::rgb() {
+            out[c] = ((self[c] as f32 / 255.0).powf(exponent) * 255.0).round() as u8;
+        }
+        out
+    }
+
+    pub fn luminance(&self) -> u8 {
+        use Color::*;
+
+        // Rec. 709 weights scaled to sum to 256
+        let sum = 54 * self[Red] as u32 + 183 * self[Green] as u32 + 19 * self[Blue] as u32;
+        ((sum + 128) >> 8) as u8
+    }
+
+    pub fn to_grayscale(&self) -> Rgba {
+        let l = self.luminance();
+        Rgba::new(l, l, l, self[Color::Alpha])
+    }
+
+    pub fn invert(&self) -> Rgba {
+        let mut out = *self;
+        for c in Color::rgb() {
+            out[c] = 255 - self[c];
+        }
+        out
+    }
+
+    pub fn lighten(&self, amount: u8) -> Rgba {
+        let mut out = *self;
+        for c in Color::rgb() {
+            out[c] = self[c].saturating_add(amount);
+        }
+        out
+    }
+
+    pub fn darken(&self, amount: u8) -> Rgba {
+        let mut out = *self;
+        for c in Color::rgb() {
+            out[c] = self[c].saturating_sub(amount);
+        }
+        out
+    }
+
+    pub fn over(self, background: Rgba) -> Self {
+        use Color::*;
+
+        match self[Alpha] {
+            255 => self,
+            0 => background,
+            alpha => {
+                let source_alpha = alpha as u32;
+                let background_alpha = (background[Alpha] as u32 * (255 - source_alpha) + 127) / 255;
+                let out_alpha = source_alpha + background_alpha;
+
+                let mut out = Rgba::new(0, 0, 0, out_alpha as u8);
+                for c in Color::rgb() {
+                    let channel = self[c] as u32 * source_alpha + background[c] as u32 * background_alpha;
+                    out[c] = ((channel + out_alpha / 2) / out_alpha) as u8;
+                }
+                out
+            }
+        }
+    }
+
+    pub fn premultiply(&self) -> Rgba {
+        let alpha = self.alpha() as u32;
+        let mut out = *self;
+        for c in Color::rgb() {
+            out[c] = ((self[c] as u32 * alpha + 127) / 255) as u8;
+        }
+        out
+    }
+
+    // colour is unrecoverable at zero alpha, so that gives transparent black
+    pub fn unpremultiply(&self) -> Rgba {
+        let alpha = self.alpha() as u32;
+        if alpha == 0 {
+            return Rgba::new(0, 0, 0, 0);
+        }
+
+        let mut out = *self;
+        for c in Color::rgb() {
+            out[c] = ((self[c] as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+        out
+    }
+
+    pub fn distance_squared(&self, other: Rgba) -> u32 {
+        Color::rgb()
+            .into_iter()
+            .map(|c| (self[c] as i32 - other[c] as i32).pow(2) as u32)
+            .sum()
+    }
+
+    pub fn distance_weighted(&self, other: Rgba) -> u32 {
+        use Color::*;
+
+        // "redmean" approximation, the red and blue weights shift with the average red
+        let mean = (self[Red] as u32 + other[Red] as u32) / 2;
+        let d = |c: Color| (self[c] as i32 - other[c] as i32).pow(2) as u32;
+
+        (((512 + mean) * d(Red)) >> 8) + 4 * d(Green) + (((767 - mean) * d(Blue)) >> 8)
+    }
+
+    pub fn nearest_in_palette(&self, palette: &[Rgba]) -> Option<(usize, Rgba)> {
+        palette
+            .iter()
+            .copied()
+            .enumerate()
+            .min_by_key(|(_, entry)| self.distance_squared(*entry))
+    }
 }
 
 fn blend_color(a: u8, b: u8, t: u8) -> u8 {
 
     let (a, b, t) = (a as u16, b as u16, t as u16);
-    (((b * t) + (a * (255 - t)) + 1) >> 8) as u8
+    (((b * t) + (a * (255 - t)) + 127) / 255) as u8
+}
+
+fn srgb_to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(channel: f32) -> u8 {
+    let c = channel.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
 }
 
 impl Index<Color> for Rgba {
     type Output = u8;
     fn index(&self, index: Color) -> &Self::Output {
-        unsafe {
-            &self.value[index as usize]
-        }
+        &self.value[index as usize]
     }
 }
 
 impl IndexMut<Color> for Rgba {
     fn index_mut(&mut self, index: Color) -> &mut Self::Output {
-        unsafe {
-            &mut self.value[index as usize]
-        }
+        &mut self.value[index as usize]
     }
 }
 
 impl Index<usize> for Rgba {
     type Output = u8;
     fn index(&self, index: usize) -> &Self::Output {
-        unsafe {
-            &self.value[index]
-        }
+        &self.value[index]
     }
 }
 
 impl IndexMut<usize> for Rgba {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        unsafe {
-            &mut self.value[index]
-        }
+        &mut self.value[index]
     }
 }
 
@@ -105,7 +364,8 @@ impl Display for Rgba {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "[")?;
         let mut is_first = true;
-        for b in unsafe {self.value} {
+        for c in Color::ALL {
+            let b = self[c];
             if is_first {
                 is_first = false;
                 write!(f, "{b}")?;
@@ -113,87 +373,109 @@ impl Display for Rgba {
                 write!(f, ", {b}")?;
             }
         }
-        Ok(())
+        write!(f, "]")
     }
 }
 
 impl From<Rgba> for u32 {
     fn from(value: Rgba) -> Self {
-        unsafe {
-            value.number
-        }
+        u32::from_le_bytes(value.value)
     }
 }
 
 impl From<u32> for Rgba {
     fn from(value: u32) -> Self {
         Self {
-            number: value
+            value: value.to_le_bytes()
         }
     }
 }
 
-impl Default for Rgba {
-    fn default() -> Self {
-        Self::new(0, 0, 0, 255)
+impl From<[u8; 4]> for Rgba {
+    fn from(value: [u8; 4]) -> Self {
+        Self::new(value[0], value[1], value[2], value[3])
     }
 }
 
-impl PartialEq for Rgba {
-    fn eq(&self, other: &Self) -> bool {
-        unsafe {
-            self.number == other.number
-        }
+impl From<(u8, u8, u8)> for Rgba {
+    fn from(value: (u8, u8, u8)) -> Self {
+        Self::new_opaque(value.0, value.1, value.2)
+    }
+}
+
+impl From<(u8, u8, u8, u8)> for Rgba {
+    fn from(value: (u8, u8, u8, u8)) -> Self {
+        Self::new(value.0, value.1, value.2, value.3)
+    }
+}
+
+impl From<Rgba> for [u8; 4] {
+    fn from(value: Rgba) -> Self {
+        [value.red(), value.green(), value.blue(), value.alpha()]
+    }
+}
+
+impl Default for Rgba {
+    fn default() -> Self {
+        Self::new(0, 0, 0, 255)
     }
 }
 
 impl std::fmt::Debug for Rgba {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        unsafe {
-            write!(f, "{:?}", self.value)
-        }
+        write!(f, "{:?}", self.value)
     }
 }
 
-impl Eq for Rgba {}
+// SAFETY: Rgba is a repr(C) wrapper around [u8; 4] with no padding,
+// and every bit pattern is a valid [u8; 4].
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for Rgba {}
+
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for Rgba {}
+
+// reinterprets in place, so the values only match From<Rgba> for u32 on little-endian targets
+#[cfg(feature = "bytemuck")]
+pub fn as_u32_slice(slice: &[Rgba]) -> &[u32] {
+    bytemuck::cast_slice(slice)
+}
 
 impl<'a> TryFrom<Chars<'a>> for Rgba {
     type Error = CharsToRgbaError;
 
-    fn try_from(mut value: Chars) -> Result<Self, Self::Error> {
-        let num = {
-            let mut out = [0; 4];
-            for i in 0..4 {
-
-                let (left, right) = (value.next(), value.next());
-
-
-                if let (Some(n1), Some(n2)) = (left, right) {
-                    if let Some(n) = hex_code_to_u8([n1, n2]) {
-                        out[i] = n;
-                    } else if i != 3 {
-                        return Err(CharsToRgbaError::InvalidStr({
-                            let mut s = String::new();
-                            s.push(n1);
-                            s.push(n2);
-                            s
-                        }
-                        ));
-                    } else {
-                        out[3] = 0xFF;
-                    }
-                } else if i != 3 {
-                    let len = if left.is_some() {i * 2 + 1} else { i * 2 };
-
-                    return Err(CharsToRgbaError::InsufficientLength(len));
-                } else {
-                    out[3] = 0xFF;
+    fn try_from(value: Chars) -> Result<Self, Self::Error> {
+        use CharsToRgbaError::*;
+
+        let chars: Vec<char> = value.collect();
+        let mut out = [0xFF; 4];
+
+        match chars.len() {
+            3 | 4 => {
+                for (i, &c) in chars.iter().enumerate() {
+                    let n = hex_code_to_u4(c).ok_or_else(|| InvalidStr(c.to_string()))?;
+                    out[i] = n * 16 + n;
                 }
+            },
+            6 | 8 => {
+                for (i, pair) in chars.chunks_exact(2).enumerate() {
+                    out[i] = hex_code_to_u8([pair[0], pair[1]]).ok_or_else(|| InvalidStr(pair.iter().collect()))?;
+                }
+            },
+            len => {
+                return Err(InsufficientLength(len));
             }
-            out
-        };
+        }
 
-        Ok(Self::new(num[0], num[1], num[2], num[3]))
+        Ok(Self::new(out[0], out[1], out[2], out[3]))
+    }
+}
+
+impl FromStr for Rgba {
+    type Err = CharsToRgbaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rgba::try_from(s.strip_prefix('#').unwrap_or(s).chars())
     }
 }
 
@@ -237,10 +519,10 @@ impl Display for CharsToRgbaError {
 
         match self {
             InsufficientLength(n) => {
-                write!(f, "Input iterator length was insufficient. Len was {n}, len requred is 6 or 8.")
+                write!(f, "Input iterator length was invalid. Len was {n}, len required is 3, 4, 6 or 8.")
             },
             InvalidStr(s) => {
-                write!(f, "{s} contians characters that are not compatiable with the hex codec, which is 0-9, or A-F.")
+                write!(f, "{s} contains characters that are not compatible with the hex codec, which is 0-9, or A-F.")
             }
         }
     }
@@ -248,6 +530,7 @@ impl Display for CharsToRgbaError {
 
 impl Error for CharsToRgbaError {}
 
+#[derive(Debug)]
 pub enum TomlToRgbaError {
     InsufficientStrLen(usize),
     InvalidStr(String),
@@ -256,6 +539,52 @@ pub enum TomlToRgbaError {
     IntConversionFail
 }
 
+impl Display for TomlToRgbaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use TomlToRgbaError::*;
+
+        match self {
+            InsufficientStrLen(n) => {
+                write!(f, "Hex string length was invalid. Len was {n}, len required is 3, 4, 6 or 8.")
+            },
+            InvalidStr(s) => {
+                write!(f, "{s} contains characters that are not compatible with the hex codec, which is 0-9, or A-F.")
+            },
+            IncorrectArrayType(n) => {
+                write!(f, "Array had {n} entries, an rgba array requires 3 or 4 integers.")
+            },
+            InvalidEntryType => {
+                write!(f, "Toml entry must be a hex string or an array of integers.")
+            },
+            IntConversionFail => {
+                write!(f, "Array entries must be integers in the range 0-255.")
+            }
+        }
+    }
+}
+
+impl Error for TomlToRgbaError {}
+
+pub fn rgba_from_toml(value: &toml::Value) -> Result<Rgba, TomlToRgbaError> {
+    match value {
+        toml::Value::String(s) => Ok(s.parse::<Rgba>()?),
+        toml::Value::Array(array) => {
+            if array.len() != 3 && array.len() != 4 {
+                return Err(TomlToRgbaError::IncorrectArrayType(array.len()));
+            }
+
+            let mut out = [0xFF; 4];
+            for (i, entry) in array.iter().enumerate() {
+                let n = entry.as_integer().ok_or(TomlToRgbaError::InvalidEntryType)?;
+                out[i] = u8::try_from(n)?;
+            }
+
+            Ok(Rgba::from(out))
+        },
+        _ => Err(TomlToRgbaError::InvalidEntryType)
+    }
+}
+
 impl From<TryFromIntError> for TomlToRgbaError {
     fn from(_value: TryFromIntError) -> Self {
         TomlToRgbaError::IntConversionFail
@@ -270,3 +599,432 @@ impl From<CharsToRgbaError> for TomlToRgbaError {
         }
     }
 }
+
+// the css colour keywords, except that names shared with the constants on Rgba resolve to the constants,
+// so "green", "gray" and "darkgray" are brighter or darker than css has them
+const NAMED_COLORS: [(&str, Rgba); 148] = [
+    ("aliceblue", Rgba::new_opaque(240, 248, 255)),
+    ("antiquewhite", Rgba::new_opaque(250, 235, 215)),
+    ("aqua", Rgba::new_opaque(0, 255, 255)),
+    ("aquamarine", Rgba::new_opaque(127, 255, 212)),
+    ("azure", Rgba::new_opaque(240, 255, 255)),
+    ("beige", Rgba::new_opaque(245, 245, 220)),
+    ("bisque", Rgba::new_opaque(255, 228, 196)),
+    ("black", Rgba::BLACK),
+    ("blanchedalmond", Rgba::new_opaque(255, 235, 205)),
+    ("blue", Rgba::BLUE),
+    ("blueviolet", Rgba::new_opaque(138, 43, 226)),
+    ("brown", Rgba::new_opaque(165, 42, 42)),
+    ("burlywood", Rgba::new_opaque(222, 184, 135)),
+    ("cadetblue", Rgba::new_opaque(95, 158, 160)),
+    ("chartreuse", Rgba::new_opaque(127, 255, 0)),
+    ("chocolate", Rgba::new_opaque(210, 105, 30)),
+    ("coral", Rgba::new_opaque(255, 127, 80)),
+    ("cornflowerblue", Rgba::new_opaque(100, 149, 237)),
+    ("cornsilk", Rgba::new_opaque(255, 248, 220)),
+    ("crimson", Rgba::new_opaque(220, 20, 60)),
+    ("cyan", Rgba::CYAN),
+    ("darkblue", Rgba::new_opaque(0, 0, 139)),
+    ("darkcyan", Rgba::new_opaque(0, 139, 139)),
+    ("darkgoldenrod", Rgba::new_opaque(184, 134, 11)),
+    ("darkgray", Rgba::DARK_GRAY),
+    ("darkgreen", Rgba::new_opaque(0, 100, 0)),
+    ("darkgrey", Rgba::DARK_GRAY),
+    ("darkkhaki", Rgba::new_opaque(189, 183, 107)),
+    ("darkmagenta", Rgba::new_opaque(139, 0, 139)),
+    ("darkolivegreen", Rgba::new_opaque(85, 107, 47)),
+    ("darkorange", Rgba::new_opaque(255, 140, 0)),
+    ("darkorchid", Rgba::new_opaque(153, 50, 204)),
+    ("darkred", Rgba::new_opaque(139, 0, 0)),
+    ("darksalmon", Rgba::new_opaque(233, 150, 122)),
+    ("darkseagreen", Rgba::new_opaque(143, 188, 143)),
+    ("darkslateblue", Rgba::new_opaque(72, 61, 139)),
+    ("darkslategray", Rgba::new_opaque(47, 79, 79)),
+    ("darkslategrey", Rgba::new_opaque(47, 79, 79)),
+    ("darkturquoise", Rgba::new_opaque(0, 206, 209)),
+    ("darkviolet", Rgba::new_opaque(148, 0, 211)),
+    ("deeppink", Rgba::new_opaque(255, 20, 147)),
+    ("deepskyblue", Rgba::new_opaque(0, 191, 255)),
+    ("dimgray", Rgba::new_opaque(105, 105, 105)),
+    ("dimgrey", Rgba::new_opaque(105, 105, 105)),
+    ("dodgerblue", Rgba::new_opaque(30, 144, 255)),
+    ("firebrick", Rgba::new_opaque(178, 34, 34)),
+    ("floralwhite", Rgba::new_opaque(255, 250, 240)),
+    ("forestgreen", Rgba::new_opaque(34, 139, 34)),
+    ("fuchsia", Rgba::new_opaque(255, 0, 255)),
+    ("gainsboro", Rgba::new_opaque(220, 220, 220)),
+    ("ghostwhite", Rgba::new_opaque(248, 248, 255)),
+    ("gold", Rgba::new_opaque(255, 215, 0)),
+    ("goldenrod", Rgba::new_opaque(218, 165, 32)),
+    ("gray", Rgba::GRAY),
+    ("green", Rgba::GREEN),
+    ("greenyellow", Rgba::new_opaque(173, 255, 47)),
+    ("grey", Rgba::GRAY),
+    ("honeydew", Rgba::new_opaque(240, 255, 240)),
+    ("hotpink", Rgba::new_opaque(255, 105, 180)),
+    ("indianred", Rgba::new_opaque(205, 92, 92)),
+    ("indigo", Rgba::new_opaque(75, 0, 130)),
+    ("ivory", Rgba::new_opaque(255, 255, 240)),
+    ("khaki", Rgba::new_opaque(240, 230, 140)),
+    ("lavender", Rgba::new_opaque(230, 230, 250)),
+    ("lavenderblush", Rgba::new_opaque(255, 240, 245)),
+    ("lawngreen", Rgba::new_opaque(124, 252, 0)),
+    ("lemonchiffon", Rgba::new_opaque(255, 250, 205)),
+    ("lightblue", Rgba::new_opaque(173, 216, 230)),
+    ("lightcoral", Rgba::new_opaque(240, 128, 128)),
+    ("lightcyan", Rgba::new_opaque(224, 255, 255)),
+    ("lightgoldenrodyellow", Rgba::new_opaque(250, 250, 210)),
+    ("lightgray", Rgba::new_opaque(211, 211, 211)),
+    ("lightgreen", Rgba::new_opaque(144, 238, 144)),
+    ("lightgrey", Rgba::new_opaque(211, 211, 211)),
+    ("lightpink", Rgba::new_opaque(255, 182, 193)),
+    ("lightsalmon", Rgba::new_opaque(255, 160, 122)),
+    ("lightseagreen", Rgba::new_opaque(32, 178, 170)),
+    ("lightskyblue", Rgba::new_opaque(135, 206, 250)),
+    ("lightslategray", Rgba::new_opaque(119, 136, 153)),
+    ("lightslategrey", Rgba::new_opaque(119, 136, 153)),
+    ("lightsteelblue", Rgba::new_opaque(176, 196, 222)),
+    ("lightyellow", Rgba::new_opaque(255, 255, 224)),
+    ("lime", Rgba::new_opaque(0, 255, 0)),
+    ("limegreen", Rgba::new_opaque(50, 205, 50)),
+    ("linen", Rgba::new_opaque(250, 240, 230)),
+    ("magenta", Rgba::MAGENTA),
+    ("maroon", Rgba::new_opaque(128, 0, 0)),
+    ("mediumaquamarine", Rgba::new_opaque(102, 205, 170)),
+    ("mediumblue", Rgba::new_opaque(0, 0, 205)),
+    ("mediumorchid", Rgba::new_opaque(186, 85, 211)),
+    ("mediumpurple", Rgba::new_opaque(147, 112, 219)),
+    ("mediumseagreen", Rgba::new_opaque(60, 179, 113)),
+    ("mediumslateblue", Rgba::new_opaque(123, 104, 238)),
+    ("mediumspringgreen", Rgba::new_opaque(0, 250, 154)),
+    ("mediumturquoise", Rgba::new_opaque(72, 209, 204)),
+    ("mediumvioletred", Rgba::new_opaque(199, 21, 133)),
+    ("midnightblue", Rgba::new_opaque(25, 25, 112)),
+    ("mintcream", Rgba::new_opaque(245, 255, 250)),
+    ("mistyrose", Rgba::new_opaque(255, 228, 225)),
+    ("moccasin", Rgba::new_opaque(255, 228, 181)),
+    ("navajowhite", Rgba::new_opaque(255, 222, 173)),
+    ("navy", Rgba::new_opaque(0, 0, 128)),
+    ("oldlace", Rgba::new_opaque(253, 245, 230)),
+    ("olive", Rgba::new_opaque(128, 128, 0)),
+    ("olivedrab", Rgba::new_opaque(107, 142, 35)),
+    ("orange", Rgba::new_opaque(255, 165, 0)),
+    ("orangered", Rgba::new_opaque(255, 69, 0)),
+    ("orchid", Rgba::new_opaque(218, 112, 214)),
+    ("palegoldenrod", Rgba::new_opaque(238, 232, 170)),
+    ("palegreen", Rgba::new_opaque(152, 251, 152)),
+    ("paleturquoise", Rgba::new_opaque(175, 238, 238)),
+    ("palevioletred", Rgba::new_opaque(219, 112, 147)),
+    ("papayawhip", Rgba::new_opaque(255, 239, 213)),
+    ("peachpuff", Rgba::new_opaque(255, 218, 185)),
+    ("peru", Rgba::new_opaque(205, 133, 63)),
+    ("pink", Rgba::new_opaque(255, 192, 203)),
+    ("plum", Rgba::new_opaque(221, 160, 221)),
+    ("powderblue", Rgba::new_opaque(176, 224, 230)),
+    ("purple", Rgba::new_opaque(128, 0, 128)),
+    ("rebeccapurple", Rgba::new_opaque(102, 51, 153)),
+    ("red", Rgba::RED),
+    ("rosybrown", Rgba::new_opaque(188, 143, 143)),
+    ("royalblue", Rgba::new_opaque(65, 105, 225)),
+    ("saddlebrown", Rgba::new_opaque(139, 69, 19)),
+    ("salmon", Rgba::new_opaque(250, 128, 114)),
+    ("sandybrown", Rgba::new_opaque(244, 164, 96)),
+    ("seagreen", Rgba::new_opaque(46, 139, 87)),
+    ("seashell", Rgba::new_opaque(255, 245, 238)),
+    ("sienna", Rgba::new_opaque(160, 82, 45)),
+    ("silver", Rgba::new_opaque(192, 192, 192)),
+    ("skyblue", Rgba::new_opaque(135, 206, 235)),
+    ("slateblue", Rgba::new_opaque(106, 90, 205)),
+    ("slategray", Rgba::new_opaque(112, 128, 144)),
+    ("slategrey", Rgba::new_opaque(112, 128, 144)),
+    ("snow", Rgba::new_opaque(255, 250, 250)),
+    ("springgreen", Rgba::new_opaque(0, 255, 127)),
+    ("steelblue", Rgba::new_opaque(70, 130, 180)),
+    ("tan", Rgba::new_opaque(210, 180, 140)),
+    ("teal", Rgba::new_opaque(0, 128, 128)),
+    ("thistle", Rgba::new_opaque(216, 191, 216)),
+    ("tomato", Rgba::new_opaque(255, 99, 71)),
+    ("turquoise", Rgba::new_opaque(64, 224, 208)),
+    ("violet", Rgba::new_opaque(238, 130, 238)),
+    ("wheat", Rgba::new_opaque(245, 222, 179)),
+    ("white", Rgba::WHITE),
+    ("whitesmoke", Rgba::new_opaque(245, 245, 245)),
+    ("yellow", Rgba::YELLOW),
+    ("yellowgreen", Rgba::new_opaque(154, 205, 50)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_endpoints_are_exact() {
+        for a in [0u8, 1, 127, 128, 254, 255] {
+            for b in [0u8, 1, 127, 128, 254, 255] {
+                assert_eq!(blend_color(a, b, 0), a);
+                assert_eq!(blend_color(a, b, 255), b);
+            }
+        }
+
+        let (from, to) = (Rgba::new(10, 20, 30, 40), Rgba::new(200, 150, 100, 50));
+        assert_eq!(from.blend(to, 0), from);
+        assert_eq!(from.blend(to, 255), to);
+    }
+
+    #[test]
+    fn display_is_bracketed() {
+        assert_eq!(Rgba::RED.to_string(), "[255, 0, 0, 255]");
+        assert_eq!(Rgba::new(1, 2, 3, 4).to_string(), "[1, 2, 3, 4]");
+    }
+
+    #[test]
+    fn parse_hex_strings() {
+        assert_eq!("#ff0080".parse::<Rgba>().unwrap(), Rgba::new(255, 0, 128, 255));
+        assert_eq!("FF0080FF".parse::<Rgba>().unwrap(), Rgba::new(255, 0, 128, 255));
+        assert_eq!("#FF008040".parse::<Rgba>().unwrap(), Rgba::new(255, 0, 128, 0x40));
+
+        assert!(matches!("GG0080".parse::<Rgba>(), Err(CharsToRgbaError::InvalidStr(s)) if s == "GG"));
+        assert!(matches!("FF0080ZZ".parse::<Rgba>(), Err(CharsToRgbaError::InvalidStr(s)) if s == "ZZ"));
+        assert!(matches!("FF0080F".parse::<Rgba>(), Err(CharsToRgbaError::InsufficientLength(7))));
+        assert!(matches!("FF0080FF00".parse::<Rgba>(), Err(CharsToRgbaError::InsufficientLength(10))));
+        assert!(matches!("#FF00F".parse::<Rgba>(), Err(CharsToRgbaError::InsufficientLength(5))));
+        assert!(matches!("".parse::<Rgba>(), Err(CharsToRgbaError::InsufficientLength(0))));
+    }
+
+    #[test]
+    fn parse_shorthand_hex() {
+        assert_eq!("f08".parse::<Rgba>().unwrap(), Rgba::new(255, 0, 136, 255));
+        assert_eq!("#f08a".parse::<Rgba>().unwrap(), Rgba::new(255, 0, 136, 0xAA));
+
+        assert!(matches!("f0g".parse::<Rgba>(), Err(CharsToRgbaError::InvalidStr(s)) if s == "g"));
+        assert!(matches!("f08z".parse::<Rgba>(), Err(CharsToRgbaError::InvalidStr(s)) if s == "z"));
+        assert!(matches!("f0".parse::<Rgba>(), Err(CharsToRgbaError::InsufficientLength(2))));
+        assert!(matches!("f08aa".parse::<Rgba>(), Err(CharsToRgbaError::InsufficientLength(5))));
+    }
+
+    #[test]
+    fn error_messages() {
+        let err = "FF0080F".parse::<Rgba>().unwrap_err();
+        assert_eq!(err.to_string(), "Input iterator length was invalid. Len was 7, len required is 3, 4, 6 or 8.");
+
+        let err = TomlToRgbaError::from("GG0080".parse::<Rgba>().unwrap_err());
+        assert_eq!(err.to_string(), "GG contains characters that are not compatible with the hex codec, which is 0-9, or A-F.");
+
+        for err in [
+            TomlToRgbaError::InsufficientStrLen(5),
+            TomlToRgbaError::IncorrectArrayType(2),
+            TomlToRgbaError::InvalidEntryType,
+            TomlToRgbaError::IntConversionFail,
+        ] {
+            assert!(!err.to_string().is_empty(), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn names_agree_with_constants() {
+        assert_eq!(Rgba::from_name("MAGENTA"), Some(Rgba::MAGENTA));
+
+        let constants = [
+            ("black", Rgba::BLACK), ("white", Rgba::WHITE), ("gray", Rgba::GRAY), ("grey", Rgba::GRAY),
+            ("darkgray", Rgba::DARK_GRAY), ("red", Rgba::RED), ("green", Rgba::GREEN), ("blue", Rgba::BLUE),
+            ("magenta", Rgba::MAGENTA), ("cyan", Rgba::CYAN), ("yellow", Rgba::YELLOW)
+        ];
+        for (name, color) in constants {
+            assert_eq!(Rgba::from_name(name), Some(color), "{name}");
+        }
+
+        assert_eq!(Rgba::from_name("CornflowerBlue"), Some(Rgba::new_opaque(100, 149, 237)));
+        assert_eq!(Rgba::from_name("notacolor"), None);
+        assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn packs_as_softbuffer_u32() {
+        assert_eq!(u32::from(Rgba::new(0x12, 0x34, 0x56, 0x78)), 0x78123456);
+        assert_eq!(u32::from(Rgba::RED) & 0x00FFFFFF, 0x00FF0000);
+        assert_eq!(Rgba::from(0xFF00FF00u32), Rgba::GREEN);
+        assert_eq!(Rgba::from(u32::from(Rgba::new(1, 2, 3, 4))), Rgba::new(1, 2, 3, 4));
+
+        assert_eq!(std::mem::size_of::<Rgba>(), 4);
+        assert_eq!(std::mem::align_of::<Rgba>(), 4);
+    }
+
+    #[cfg(all(feature = "bytemuck", target_endian = "little"))]
+    #[test]
+    fn casts_slices_in_place() {
+        let colors = [Rgba::RED, Rgba::new(1, 2, 3, 4)];
+        assert_eq!(as_u32_slice(&colors), &[u32::from(colors[0]), u32::from(colors[1])]);
+
+        let bytes: &[u8] = bytemuck::cast_slice(&colors);
+        assert_eq!(&bytes[4..], &[3, 2, 1, 4]);
+    }
+
+
+    #[test]
+    fn hex_strings_round_trip() {
+        let color = Rgba::new(255, 0, 128, 255);
+        assert_eq!(color.to_hex_string(), "FF0080FF");
+        assert_eq!(color.to_hex_string_rgb(), "FF0080");
+        assert_eq!(Rgba::try_from(color.to_hex_string().chars()).unwrap(), color);
+
+        let color = Rgba::new(1, 2, 3, 4);
+        assert_eq!(Rgba::try_from(color.to_hex_string().chars()).unwrap(), color);
+        // the rgb form keeps a non opaque alpha so it still round trips
+        assert_eq!(color.to_hex_string_rgb(), "01020304");
+        assert_eq!(Rgba::try_from(color.to_hex_string_rgb().chars()).unwrap(), color);
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        for color in [Rgba::RED, Rgba::GREEN, Rgba::BLUE, Rgba::MAGENTA, Rgba::CYAN, Rgba::YELLOW, Rgba::new(200, 30, 90, 255)] {
+            let (h, s, v) = color.to_hsv();
+            assert_eq!(Rgba::from_hsv(h, s, v), color, "{color} {h} {s} {v}");
+        }
+
+        assert_eq!(Rgba::BLUE.to_hsv(), (240.0, 1.0, 1.0));
+        assert_eq!(Rgba::from_hsv(720.0, 1.0, 1.0), Rgba::RED);
+        assert_eq!(Rgba::from_hsv(-120.0, 1.0, 1.0), Rgba::BLUE);
+        assert_eq!(Rgba::from_hsv(33.0, 0.0, 0.5), Rgba::new(128, 128, 128, 255));
+    }
+
+    #[test]
+    fn luminance_and_grayscale() {
+        assert!(Rgba::GREEN.luminance() > 3 * Rgba::BLUE.luminance());
+        assert_eq!(Rgba::WHITE.luminance(), 255);
+        assert_eq!(Rgba::BLACK.luminance(), 0);
+        assert_eq!(Rgba::new(255, 0, 0, 7).to_grayscale(), Rgba::new(54, 54, 54, 7));
+    }
+
+    #[test]
+    fn invert_keeps_alpha() {
+        let color = Rgba::new(1, 2, 3, 4);
+        assert_eq!(color.invert(), Rgba::new(254, 253, 252, 4));
+        assert_eq!(color.invert().invert(), color);
+        assert_eq!(Rgba::BLACK.invert(), Rgba::WHITE);
+    }
+
+    #[test]
+    fn lighten_and_darken_saturate() {
+        assert_eq!(Rgba::WHITE.lighten(40), Rgba::WHITE);
+        assert_eq!(Rgba::BLACK.darken(40), Rgba::BLACK);
+        assert_eq!(Rgba::new(250, 10, 100, 9).lighten(10), Rgba::new(255, 20, 110, 9));
+        assert_eq!(Rgba::new(250, 10, 100, 9).darken(20), Rgba::new(230, 0, 80, 9));
+    }
+
+    #[test]
+    fn lerp_clamps_t() {
+        let (from, to) = (Rgba::new(1, 2, 3, 4), Rgba::new(200, 100, 50, 250));
+        assert_eq!(from.lerp(to, 0.0), from);
+        assert_eq!(from.lerp(to, 1.0), to);
+        assert_eq!(from.lerp(to, 7.0), to);
+        assert_eq!(Rgba::BLACK.lerp(Rgba::WHITE, 0.5), Rgba::new(128, 128, 128, 255));
+    }
+
+    #[test]
+    fn blend_interpolates_alpha() {
+        let color = Rgba::new(0, 0, 0, 0).blend(Rgba::new(0, 0, 0, 255), 128);
+        assert_eq!(color[Color::Alpha], 128);
+    }
+
+
+    #[test]
+    fn channel_accessors() {
+        let color = Rgba::new(1, 2, 3, 4);
+        assert_eq!((color.red(), color.green(), color.blue(), color.alpha()), (1, 2, 3, 4));
+        assert_eq!(color.with_alpha(9), Rgba::new(1, 2, 3, 9));
+    }
+
+
+    #[test]
+    fn array_and_tuple_conversions() {
+        let color = Rgba::new(1, 2, 3, 4);
+        assert_eq!(Rgba::from([1, 2, 3, 4]), color);
+        assert_eq!(<[u8; 4]>::from(color), [1, 2, 3, 4]);
+        assert_eq!(Rgba::from((1, 2, 3)), Rgba::new(1, 2, 3, 255));
+        assert_eq!(Rgba::from((1, 2, 3, 4)), color);
+    }
+
+    #[test]
+    fn parse_toml_values() {
+        let table: toml::Table = toml::from_str(concat!(
+            "hex = \"#FF0080\"\nrgb = [1, 2, 3]\nrgba = [1, 2, 3, 4]\n",
+            "short = [1, 2]\nlarge = [1, 2, 300]\nint = 5\nmixed = [1, \"x\", 3, 4]"
+        )).unwrap();
+
+        assert_eq!(rgba_from_toml(&table["hex"]).unwrap(), Rgba::new(255, 0, 128, 255));
+        assert_eq!(rgba_from_toml(&table["rgb"]).unwrap(), Rgba::new(1, 2, 3, 255));
+        assert_eq!(rgba_from_toml(&table["rgba"]).unwrap(), Rgba::new(1, 2, 3, 4));
+
+        assert!(matches!(rgba_from_toml(&table["short"]), Err(TomlToRgbaError::IncorrectArrayType(2))));
+        assert!(matches!(rgba_from_toml(&table["large"]), Err(TomlToRgbaError::IntConversionFail)));
+        assert!(matches!(rgba_from_toml(&table["int"]), Err(TomlToRgbaError::InvalidEntryType)));
+        assert!(matches!(rgba_from_toml(&table["mixed"]), Err(TomlToRgbaError::InvalidEntryType)));
+    }
+
+
+    #[test]
+    fn all_lists_each_channel_once() {
+        for channel in [Color::Red, Color::Green, Color::Blue, Color::Alpha] {
+            assert_eq!(Color::ALL.iter().filter(|&&entry| entry == channel).count(), 1);
+        }
+    }
+
+
+    #[test]
+    fn hex_u32_literals() {
+        const PACKED: Rgba = Rgba::from_hex_u32(0x3366FFCC);
+        assert_eq!(PACKED, Rgba::new(0x33, 0x66, 0xFF, 0xCC));
+    }
+
+
+    #[test]
+    fn distances_ignore_alpha() {
+        assert_eq!(Rgba::RED.distance_squared(Rgba::RED), 0);
+        assert_eq!(Rgba::RED.distance_squared(Rgba::RED.with_alpha(0)), 0);
+        assert!(Rgba::RED.distance_squared(Rgba::MAGENTA) < Rgba::RED.distance_squared(Rgba::GREEN));
+        assert!(Rgba::RED.distance_weighted(Rgba::MAGENTA) < Rgba::RED.distance_weighted(Rgba::GREEN));
+    }
+
+
+    #[test]
+    fn nearest_in_palette() {
+        let palette = [Rgba::BLACK, Rgba::RED, Rgba::WHITE, Rgba::BLUE];
+        assert_eq!(Rgba::new_opaque(250, 245, 240).nearest_in_palette(&palette), Some((2, Rgba::WHITE)));
+        assert_eq!(Rgba::RED.nearest_in_palette(&[]), None);
+    }
+
+    #[test]
+    fn premultiply_round_trip() {
+        for alpha in [255u8, 200, 128, 64, 17] {
+            let premultiplied = Rgba::new(250, 100, 3, alpha).premultiply();
+            assert_eq!(premultiplied.alpha(), alpha);
+
+            let restored = premultiplied.unpremultiply();
+            let tolerance = 255 / alpha as i32 + 1;
+            for (channel, expected) in [(restored.red(), 250), (restored.green(), 100), (restored.blue(), 3)] {
+                assert!((channel as i32 - expected).abs() <= tolerance, "{alpha} {channel} {expected}");
+            }
+        }
+
+        assert_eq!(Rgba::new(10, 20, 30, 0).premultiply().unpremultiply(), Rgba::new(0, 0, 0, 0));
+        assert_eq!(Rgba::new(255, 255, 255, 128).premultiply(), Rgba::new(128, 128, 128, 128));
+        assert_eq!(Rgba::new(200, 255, 255, 10).unpremultiply().red(), 255);
+    }
+
+    #[test]
+    fn linear_round_trip() {
+        for v in 0..=255u8 {
+            let color = Rgba::new(v, 255 - v, v / 2, v);
+            assert_eq!(Rgba::from_linear(color.to_linear()), color, "{v}");
+        }
+
+        let gray = Rgba::new(128, 128, 128, 255).to_linear();
+        assert!((gray[0] - 0.2158).abs() < 0.005);
+        assert_eq!(gray[3], 1.0);
+        assert_eq!(Rgba::RED.to_linear(), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(Rgba::from_linear([2.0, -1.0, f32::NAN, 0.5]), Rgba::new(255, 0, 0, 128));
+
+        assert_eq!(Rgba::new(64, 0, 255, 9).gamma_correct(1.0), Rgba::new(64, 0, 255, 9));
+        assert!(Rgba::new(64, 0, 255, 9).gamma_correct(2.2).red() > 64);
+    }
+}